@@ -0,0 +1,66 @@
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use dualsense_tray::dualsense::proto::{
+    DS_INPUT_REPORT_BT, DS_INPUT_REPORT_BT_SIZE, DS_INPUT_REPORT_USB, DS_INPUT_REPORT_USB_SIZE,
+    DualSenseInputReport,
+};
+
+fn usb_report() -> [u8; DS_INPUT_REPORT_USB_SIZE] {
+    let mut buf = [0u8; DS_INPUT_REPORT_USB_SIZE];
+    buf[0] = DS_INPUT_REPORT_USB;
+    buf[1..5].copy_from_slice(&[0x20, 0x80, 0xE0, 0x80]);
+    buf[8] = 0x28; // Cross, d-pad neutral
+    buf
+}
+
+fn bt_report() -> [u8; DS_INPUT_REPORT_BT_SIZE] {
+    let mut buf = [0u8; DS_INPUT_REPORT_BT_SIZE];
+    buf[0] = DS_INPUT_REPORT_BT;
+    buf[2..6].copy_from_slice(&[0x20, 0x80, 0xE0, 0x80]);
+    buf[9] = 0x28;
+    buf
+}
+
+/// Byte by byte equivalent of `parse` followed by `left_stick`, as a baseline
+fn parse_left_stick_by_hand(data: &[u8]) -> Option<(f32, f32)> {
+    let offset = match *data.first()? {
+        DS_INPUT_REPORT_USB => 1,
+        DS_INPUT_REPORT_BT => 2,
+        _ => return None,
+    };
+    let x = *data.get(offset)? as f32;
+    let y = *data.get(offset + 1)? as f32;
+    Some((
+        ((x - 128.0) / 127.0).clamp(-1.0, 1.0),
+        ((128.0 - y) / 127.0).clamp(-1.0, 1.0),
+    ))
+}
+
+fn parse(c: &mut Criterion) {
+    let usb = usb_report();
+    let bt = bt_report();
+
+    c.bench_function("parse usb", |b| {
+        b.iter(|| DualSenseInputReport::parse(black_box(&usb)))
+    });
+    c.bench_function("parse bt", |b| {
+        b.iter(|| DualSenseInputReport::parse(black_box(&bt)))
+    });
+
+    let report = DualSenseInputReport::parse(&usb).unwrap();
+    c.bench_function("pressed_buttons", |b| {
+        b.iter(|| black_box(report).pressed_buttons())
+    });
+    c.bench_function("left_stick", |b| b.iter(|| black_box(report).left_stick()));
+
+    c.bench_function("parse + left_stick", |b| {
+        b.iter(|| DualSenseInputReport::parse(black_box(&usb)).map(|report| report.left_stick()))
+    });
+    c.bench_function("parse + left_stick by hand", |b| {
+        b.iter(|| parse_left_stick_by_hand(black_box(&usb)))
+    });
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
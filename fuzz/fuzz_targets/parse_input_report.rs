@@ -0,0 +1,42 @@
+//! Feeds arbitrary bytes to `DualSenseInputReport::parse` and every accessor of the result.
+//!
+//! Run with a nightly toolchain from the repository root:
+//!
+//! ```sh
+//! cargo install cargo-fuzz
+//! cargo +nightly fuzz run parse_input_report
+//! ```
+#![no_main]
+
+use dualsense_tray::dualsense::proto::{DeadzoneShape, DualSenseInputReport};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some(report) = DualSenseInputReport::parse(data) else {
+        return;
+    };
+
+    let _ = report.battery();
+    let _ = report.pressed_buttons();
+    let _ = report.dpad();
+    let _ = (report.left_trigger(), report.right_trigger());
+    let _ = (report.left_stick(), report.right_stick());
+    let _ = (report.left_stick_polar(), report.right_stick_polar());
+    for shape in [
+        DeadzoneShape::Circular,
+        DeadzoneShape::Square,
+        DeadzoneShape::Cross,
+    ] {
+        let _ = report.left_stick_with_deadzone(0.1, shape);
+    }
+    let _ = (report.raw_gyro(), report.raw_accel(), report.seq_number());
+    let _ = report.to_feature_vec();
+    let _ = report.to_string();
+
+    // x() and y() assert the bounds in debug builds, so only valid points are decoded
+    for point in report.sorted_touch_points() {
+        if point.is_active() && point.is_valid() {
+            let _ = (point.id(), point.x(), point.y());
+        }
+    }
+});
@@ -0,0 +1,187 @@
+use std::{
+    fmt, fs, io,
+    net::SocketAddr,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use crate::dualsense::proto::FirmwareVersion;
+
+/// Port of the Prometheus metrics, on localhost by default
+pub const DEFAULT_METRICS_PORT: u16 = 9090;
+
+/// Settings of the tray application, as opposed to the per-device `DualSenseConfig`.
+///
+/// Stored as `key = value` lines, unknown keys and invalid values are ignored with a warning
+/// so an old or hand edited file never keeps the tray from starting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppConfig {
+    /// Where the Prometheus metrics are served with the `metrics` feature
+    pub metrics_addr: SocketAddr,
+    /// Where the latest firmware version is fetched from with the `firmware_check` feature.
+    /// Nothing publishes it, so there is no check until one is set.
+    pub firmware_url: Option<String>,
+    /// Latest firmware version seen at `firmware_url`, kept between checks
+    pub latest_firmware: Option<FirmwareVersion>,
+    /// Stored as seconds since the Unix epoch
+    pub last_firmware_check: Option<SystemTime>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            metrics_addr: SocketAddr::from(([127, 0, 0, 1], DEFAULT_METRICS_PORT)),
+            firmware_url: None,
+            latest_firmware: None,
+            last_firmware_check: None,
+        }
+    }
+}
+
+impl AppConfig {
+    /// `dualsense-tray.conf` in the per-user config directory, `None` if it can't be found
+    pub fn path() -> Option<PathBuf> {
+        let dir = if cfg!(target_os = "windows") {
+            PathBuf::from(std::env::var_os("APPDATA")?)
+        } else if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME") {
+            PathBuf::from(dir)
+        } else {
+            PathBuf::from(std::env::var_os("HOME")?).join(".config")
+        };
+        Some(dir.join("dualsense-tray.conf"))
+    }
+
+    /// The config at `path()`, the defaults if there is none
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                tracing::warn!("Failed to read {}: {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes the config to `path()`, comments and unknown keys of the file are not kept
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_string())
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut config = Self::default();
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                tracing::warn!("Ignoring config line without a value: {line}");
+                continue;
+            };
+            if !config.set(key.trim(), value.trim()) {
+                tracing::warn!("Ignoring invalid config line: {line}");
+            }
+        }
+        config
+    }
+
+    /// False on unknown keys and invalid values
+    fn set(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            "metrics_addr" => match value.parse() {
+                Ok(addr) => self.metrics_addr = addr,
+                Err(_) => return false,
+            },
+            "firmware_url" => {
+                self.firmware_url = Some(value).filter(|url| !url.is_empty()).map(str::to_owned)
+            }
+            "latest_firmware" => match value.parse() {
+                Ok(version) => self.latest_firmware = Some(version),
+                Err(_) => return false,
+            },
+            "last_firmware_check" => match value.parse() {
+                Ok(secs) => {
+                    self.last_firmware_check =
+                        Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+                }
+                Err(_) => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// The `key = value` lines read by `parse`
+impl fmt::Display for AppConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "metrics_addr = {}", self.metrics_addr)?;
+        if let Some(url) = &self.firmware_url {
+            writeln!(f, "firmware_url = {url}")?;
+        }
+        if let Some(version) = self.latest_firmware {
+            writeln!(f, "latest_firmware = {version}")?;
+        }
+        if let Some(last_check) = self.last_firmware_check {
+            let secs = last_check
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            writeln!(f, "last_firmware_check = {secs}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_lines_keep_defaults() {
+        let config = AppConfig::parse("# comment\n\nunknown = 1\nno value\n");
+        assert_eq!(config, AppConfig::default());
+    }
+
+    #[test]
+    fn parse_metrics_addr() {
+        assert_eq!(
+            AppConfig::default().metrics_addr,
+            SocketAddr::from(([127, 0, 0, 1], 9090))
+        );
+        let config = AppConfig::parse("metrics_addr = 0.0.0.0:9100\n");
+        assert_eq!(config.metrics_addr, SocketAddr::from(([0, 0, 0, 0], 9100)));
+        let config = AppConfig::parse("metrics_addr = nowhere\n");
+        assert_eq!(config, AppConfig::default());
+    }
+
+    #[test]
+    fn saved_lines_parse_back() {
+        assert_eq!(
+            AppConfig::parse(&AppConfig::default().to_string()),
+            AppConfig::default()
+        );
+        let config = AppConfig {
+            firmware_url: Some("http://localhost/firmware.json".to_owned()),
+            latest_firmware: Some(FirmwareVersion(0x0224)),
+            last_firmware_check: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_760_000_000)),
+            ..Default::default()
+        };
+        assert_eq!(AppConfig::parse(&config.to_string()), config);
+    }
+
+    #[test]
+    fn empty_firmware_url_is_unset() {
+        assert_eq!(AppConfig::default().firmware_url, None);
+        let config = AppConfig::parse("firmware_url =\n");
+        assert_eq!(config.firmware_url, None);
+    }
+}
@@ -0,0 +1,29 @@
+#[cfg(target_os = "linux")]
+fn main() -> std::io::Result<()> {
+    use std::path::PathBuf;
+
+    use dualsense_tray::platform::linux::{DEFAULT_UDEV_RULES_PATH, write_udev_rules};
+
+    let path = std::env::args_os()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_UDEV_RULES_PATH));
+
+    if let Err(err) = write_udev_rules(&path) {
+        eprintln!("Failed to write {}: {err}", path.display());
+        eprintln!("Writing to /etc/udev/rules.d requires root, try running with sudo");
+        return Err(err);
+    }
+
+    println!("Wrote udev rules to {}", path.display());
+    println!("To apply them without rebooting, run:");
+    println!("  sudo udevadm control --reload-rules && sudo udevadm trigger");
+    println!("Then make sure your user is in the input group and log in again:");
+    println!("  sudo usermod -aG input $USER");
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    eprintln!("dualsense-setup is only needed on Linux");
+}
@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_hid::DeviceId;
+use async_io::Timer;
+
+use crate::device_manager::{DeviceManager, DeviceManagerEvent};
+
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+type EventHandler = Arc<dyn Fn(DeviceManagerEvent) + Send + Sync + 'static>;
+
+/// A `DeviceManager` whose `Connected` events are held back for a while, so a wiggled cable
+/// doesn't make the tray menu flicker.
+pub struct DebouncedDeviceManager {
+    inner: DeviceManager,
+    delay: Duration,
+}
+
+impl DebouncedDeviceManager {
+    pub fn new(inner: DeviceManager) -> Self {
+        Self::with_delay(inner, DEFAULT_DEBOUNCE)
+    }
+
+    pub fn with_delay(inner: DeviceManager, delay: Duration) -> Self {
+        Self { inner, delay }
+    }
+
+    pub fn set_event_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(DeviceManagerEvent) + Send + Sync + 'static,
+    {
+        let debouncer = EventDebouncer::new(self.delay, handler);
+        self.inner
+            .set_event_handler(move |event| debouncer.handle(event));
+    }
+}
+
+impl Deref for DebouncedDeviceManager {
+    type Target = DeviceManager;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+struct Pending {
+    generation: u64,
+    events: Vec<DeviceManagerEvent>,
+}
+
+#[derive(Default)]
+struct DebouncerState {
+    generation: u64,
+    pending: HashMap<DeviceId, Pending>,
+}
+
+/// Delays `Connected` events, cancelling them together with a `Disconnected` that arrives in
+/// the meantime. Other events of a pending device are held until it is delivered.
+#[derive(Clone)]
+struct EventDebouncer {
+    delay: Duration,
+    handler: EventHandler,
+    state: Arc<Mutex<DebouncerState>>,
+}
+
+impl EventDebouncer {
+    fn new<F>(delay: Duration, handler: F) -> Self
+    where
+        F: Fn(DeviceManagerEvent) + Send + Sync + 'static,
+    {
+        Self {
+            delay,
+            handler: Arc::new(handler),
+            state: Arc::new(Mutex::new(DebouncerState::default())),
+        }
+    }
+
+    fn handle(&self, event: DeviceManagerEvent) {
+        let Some(device_id) = event_device_id(&event).cloned() else {
+            (self.handler)(event);
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+
+        match event {
+            DeviceManagerEvent::Connected { .. } => {
+                state.generation += 1;
+                let generation = state.generation;
+                state.pending.insert(
+                    device_id.clone(),
+                    Pending {
+                        generation,
+                        events: vec![event],
+                    },
+                );
+
+                let debouncer = self.clone();
+                smol::spawn(async move {
+                    Timer::after(debouncer.delay).await;
+                    debouncer.flush(&device_id, generation);
+                })
+                .detach();
+            }
+            DeviceManagerEvent::Disconnected(_) if state.pending.contains_key(&device_id) => {
+                state.pending.remove(&device_id);
+            }
+            event => match state.pending.get_mut(&device_id) {
+                Some(pending) => pending.events.push(event),
+                None => {
+                    drop(state);
+                    (self.handler)(event);
+                }
+            },
+        }
+    }
+
+    fn flush(&self, device_id: &DeviceId, generation: u64) {
+        let events = {
+            let mut state = self.state.lock().unwrap();
+            match state.pending.get(device_id) {
+                Some(pending) if pending.generation == generation => state
+                    .pending
+                    .remove(device_id)
+                    .map(|pending| pending.events),
+                _ => None,
+            }
+        };
+
+        for event in events.into_iter().flatten() {
+            (self.handler)(event);
+        }
+    }
+}
+
+/// `None` for events that aren't about a single device, those are never held back
+fn event_device_id(event: &DeviceManagerEvent) -> Option<&DeviceId> {
+    match event {
+        DeviceManagerEvent::Connected { id, .. } | DeviceManagerEvent::StatsUpdate { id, .. } => {
+            Some(id)
+        }
+        DeviceManagerEvent::Disconnected(id)
+        | DeviceManagerEvent::BatteryUpdate(id, _)
+        | DeviceManagerEvent::DeviceIdle(id)
+        | DeviceManagerEvent::DeviceActive(id)
+        | DeviceManagerEvent::PlayerNumberAssigned(id, _)
+        | DeviceManagerEvent::HealthCheckFailed(id)
+        | DeviceManagerEvent::FirmwareUpdateAvailable(id, _) => Some(id),
+        DeviceManagerEvent::Error(_) => None,
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use macro_rules_attribute::apply;
+    use smol_macros::test;
+
+    use super::*;
+    use crate::dualsense::async_hid::DualSenseConnectionType;
+
+    #[apply(test!)]
+    async fn connect_disconnect_connect_delivers_one_connected() {
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let debouncer = EventDebouncer::new(DEFAULT_DEBOUNCE, {
+            let delivered = delivered.clone();
+            move |event| delivered.lock().unwrap().push(event)
+        });
+
+        let id = DeviceId::DevPath("/dev/hidraw0".into());
+        let connected = || DeviceManagerEvent::Connected {
+            id: id.clone(),
+            name: "DualSense USB".to_string(),
+            serial: None,
+            connection_type: DualSenseConnectionType::USB,
+        };
+
+        debouncer.handle(connected());
+        Timer::after(Duration::from_millis(100)).await;
+        debouncer.handle(DeviceManagerEvent::Disconnected(id.clone()));
+        Timer::after(Duration::from_millis(100)).await;
+        debouncer.handle(connected());
+        debouncer.handle(DeviceManagerEvent::BatteryUpdate(id.clone(), (50, false)));
+        assert!(delivered.lock().unwrap().is_empty());
+
+        Timer::after(DEFAULT_DEBOUNCE + Duration::from_millis(100)).await;
+        let delivered = delivered.lock().unwrap();
+        assert_eq!(delivered.len(), 2);
+        assert!(matches!(delivered[0], DeviceManagerEvent::Connected { .. }));
+        assert!(matches!(
+            delivered[1],
+            DeviceManagerEvent::BatteryUpdate(_, (50, false))
+        ));
+    }
+}
@@ -1,85 +1,474 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    any::Any,
+    collections::{HashMap, hash_map::Entry},
+    future::Future,
+    panic::AssertUnwindSafe,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use async_hid::{DeviceEvent, DeviceId, HidBackend, HidError, HidResult};
-use futures_lite::StreamExt;
-use smol::lock::Mutex;
+use async_hid::{DeviceEvent, DeviceId, HidBackend, HidError};
+use async_io::Timer;
+use futures_lite::{FutureExt, StreamExt};
+use smol::{
+    Executor, LocalExecutor, Task,
+    channel::{self, Receiver, Sender, TrySendError},
+    lock::{Mutex, Semaphore},
+};
 
-use crate::dualsense::async_hid::DualSense;
+use crate::{
+    dualsense::{
+        async_hid::{DualSense, DualSenseConnectionType, DualSenseLifecycle},
+        calibration::StickCalibrator,
+        config::DualSenseConfig,
+        error::{DualSenseError, DualSenseResult},
+        idle::IdleDetector,
+        proto::{
+            ChargingStatus, DeviceInfoReport, DualSenseInputReport, DualSenseOutputReport,
+            DualSenseOutputReportBuilder, FirmwareVersion, LightbarEffect, LightbarPreset,
+            OutputFeatures, check_firmware_update, from_preset,
+        },
+        sink::DualSenseSink,
+    },
+    panic::without_reset,
+};
 
-#[derive(Debug)]
+/// Lightbar shown while a controller is charging
+const CHARGING_LIGHTBAR: LightbarEffect = LightbarEffect::Pulse {
+    r: 255,
+    g: 140,
+    b: 0,
+    rate: 20,
+};
+const LIGHTBAR_FRAME_INTERVAL: Duration = Duration::from_millis(50);
+/// Lightbar brightness of idle controllers, in percent
+const IDLE_LIGHTBAR_PERCENT: u16 = 10;
+/// How long dropping the manager waits for the controllers to be reset
+#[cfg(feature = "reset_on_drop")]
+const DROP_RESET_TIMEOUT: Duration = Duration::from_secs(1);
+/// Reports read on every status poll, enough to measure the report rate
+const STATUS_POLL_REPORTS: usize = 16;
+/// Time between health checks in `watch_status`
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Player numbers the controller can show on its player LEDs
+pub const MAX_PLAYERS: u8 = 4;
+
+#[derive(Debug, Clone)]
 pub enum DeviceManagerEvent {
-    Connected(DeviceId, String),
+    Connected {
+        id: DeviceId,
+        name: String,
+        serial: Option<String>,
+        connection_type: DualSenseConnectionType,
+    },
     Disconnected(DeviceId),
     BatteryUpdate(DeviceId, (u8, bool)), // percentage, charging
+    DeviceIdle(DeviceId),
+    DeviceActive(DeviceId),
+    /// Report rate measured while reading a burst of reports, on every status poll and when
+    /// recalibrating
+    StatsUpdate {
+        id: DeviceId,
+        report_rate_hz: f32,
+        /// 95th percentile of the delay beyond the expected report interval
+        p95_lag_ms: f32,
+    },
+    /// A background task panicked, the message is also logged
+    Error(String),
+    /// Sent right after `Connected`, the number is kept until the device disconnects. Not sent
+    /// while `MAX_PLAYERS` devices already have a number.
+    PlayerNumberAssigned(DeviceId, u8),
+    /// The device is open but didn't answer a health check, see `DualSense::health_check`
+    HealthCheckFailed(DeviceId),
+    /// The device runs an older firmware than the latest given to `set_latest_firmware`
+    FirmwareUpdateAvailable(DeviceId, FirmwareVersion),
+}
+
+/// Which connection to keep when a controller is reachable over both USB and Bluetooth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConnectionPreference {
+    /// USB has lower latency, so it replaces an open BT connection
+    #[default]
+    PreferUSB,
+    PreferBT,
+    FirstSeen,
+    /// Keeps both connections as separate devices, deduplicating by serial and connection type.
+    ///
+    /// Meant for setups where the controller is used over USB and BT from different hosts.
+    /// Most operating systems release the Bluetooth link once the cable is plugged in, so on
+    /// a single host both connections are rarely visible at the same time.
+    Independent,
+}
+
+impl ConnectionPreference {
+    fn prefers(&self, new: DualSenseConnectionType, existing: DualSenseConnectionType) -> bool {
+        match self {
+            Self::PreferUSB => {
+                new == DualSenseConnectionType::USB && existing == DualSenseConnectionType::BT
+            }
+            Self::PreferBT => {
+                new == DualSenseConnectionType::BT && existing == DualSenseConnectionType::USB
+            }
+            Self::FirstSeen | Self::Independent => false,
+        }
+    }
+}
+
+/// Connections left closed in favour of another connection to the same controller, so they
+/// can be opened once that one disconnects
+#[derive(Debug, Default)]
+struct SkippedDuplicates {
+    /// By skipped id, the id of the connection kept instead
+    kept_by_skipped: HashMap<DeviceId, DeviceId>,
+}
+
+impl SkippedDuplicates {
+    /// Applies `preference` to a `new` connection of a controller already open as `existing`
+    /// and remembers the one left closed. `true` if `new` replaces `existing`.
+    fn resolve(
+        &mut self,
+        preference: ConnectionPreference,
+        (new_id, new_type): (&DeviceId, DualSenseConnectionType),
+        (existing_id, existing_type): (&DeviceId, DualSenseConnectionType),
+    ) -> bool {
+        if !preference.prefers(new_type, existing_type) {
+            self.kept_by_skipped
+                .insert(new_id.clone(), existing_id.clone());
+            return false;
+        }
+        for kept in self.kept_by_skipped.values_mut() {
+            if kept == existing_id {
+                *kept = new_id.clone();
+            }
+        }
+        self.kept_by_skipped
+            .insert(existing_id.clone(), new_id.clone());
+        true
+    }
+
+    /// Forgets `device_id`, returning the connections skipped in its favour
+    fn disconnected(&mut self, device_id: &DeviceId) -> Vec<DeviceId> {
+        self.kept_by_skipped.remove(device_id);
+        self.kept_by_skipped
+            .extract_if(|_, kept| kept == device_id)
+            .map(|(skipped, _)| skipped)
+            .collect()
+    }
+}
+
+/// Rumble played when a controller is opened, see `DeviceManager::set_connection_feedback`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ConnectionFeedback {
+    duration: Duration,
+    weak: u8,
+    strong: u8,
+}
+
+type EventHandler = Arc<Box<dyn Fn(DeviceManagerEvent) + Send + Sync + 'static>>;
+
+/// Delivers events to the handler and to every subscriber
+#[derive(Clone, Default)]
+struct EventEmitter {
+    handler: Option<EventHandler>,
+    subscribers: Arc<std::sync::Mutex<Vec<Sender<DeviceManagerEvent>>>>,
+}
+
+impl EventEmitter {
+    /// Status polls are skipped when nobody would receive their events
+    fn has_listeners(&self) -> bool {
+        self.handler.is_some()
+            || self
+                .subscribers
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|sender| !sender.is_closed())
+    }
+
+    fn emit(&self, event: DeviceManagerEvent) {
+        // subscribers whose receiver was dropped are removed here
+        self.subscribers.lock().unwrap().retain(|sender| {
+            !matches!(sender.try_send(event.clone()), Err(TrySendError::Closed(_)))
+        });
+        if let Some(handler) = &self.handler {
+            handler(event);
+        }
+    }
 }
 
 pub struct DeviceManager {
     hid: HidBackend,
+    executor: Arc<Executor<'static>>,
     opened_devices: Mutex<HashMap<DeviceId, Arc<DualSense>>>,
-    event_handler: Option<Arc<Box<dyn Fn(DeviceManagerEvent) + Send + Sync + 'static>>>,
+    connected_at: Mutex<HashMap<DeviceId, Instant>>,
+    player_numbers: Mutex<HashMap<DeviceId, u8>>,
+    calibrators: Mutex<HashMap<DeviceId, StickCalibrator>>,
+    device_configs: Mutex<HashMap<DeviceId, DualSenseConfig>>,
+    /// Used to open devices, and for devices without an entry in `device_configs`
+    config: DualSenseConfig,
+    /// By serial number, so they survive reconnects and switching between USB and BT
+    display_names: Mutex<HashMap<String, String>>,
+    idle_detectors: Mutex<HashMap<DeviceId, IdleDetector>>,
+    poll_limit: Semaphore,
+    connection_preference: ConnectionPreference,
+    skipped_duplicates: Mutex<SkippedDuplicates>,
+    sinks: Mutex<HashMap<DeviceId, Arc<dyn DualSenseSink>>>,
+    lightbar_tasks: Mutex<HashMap<DeviceId, Task<Option<()>>>>,
+    /// Last polled charging status, to notice when a device becomes fully charged
+    charging_statuses: Mutex<HashMap<DeviceId, ChargingStatus>>,
+    /// Last lightbar color written with `write_output_report`, restored after the charging
+    /// animation and once an idle device is used again
+    lightbars: Mutex<HashMap<DeviceId, (u8, u8, u8)>>,
+    dim_idle_lightbar: bool,
+    latest_firmware: Mutex<Option<FirmwareVersion>>,
+    connection_feedback: Option<ConnectionFeedback>,
+    events: EventEmitter,
 }
 
 impl DeviceManager {
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use dualsense_tray::device_manager::{DeviceManager, DeviceManagerEvent};
+    /// let mut device_manager = DeviceManager::new();
+    /// device_manager.set_event_handler(|event| {
+    ///     if let DeviceManagerEvent::BatteryUpdate(_, (capacity, _)) = event {
+    ///         println!("battery at {capacity}%");
+    ///     }
+    /// });
+    /// smol::block_on(async {
+    ///     device_manager.open_all_devices().await?;
+    ///     device_manager.watch_pnp().await
+    /// })?;
+    /// # Ok::<(), dualsense_tray::dualsense::error::DualSenseError>(())
+    /// ```
     pub fn new() -> Self {
+        Self::with_config(DualSenseConfig::default())
+    }
+
+    pub fn with_config(config: DualSenseConfig) -> Self {
+        let mut device_manager = Self::new_with_executor(Arc::new(Executor::new()));
+        device_manager.apply_config(config);
+        device_manager
+    }
+
+    /// Background work like polls and lightbar animations is spawned on `executor`, which
+    /// only makes progress while it is driven, e.g. through `DeviceManager::run`.
+    pub fn new_with_executor(executor: Arc<Executor<'static>>) -> Self {
         Self {
             hid: HidBackend::default(),
+            executor,
             opened_devices: Mutex::new(HashMap::new()),
-            event_handler: None,
+            connected_at: Mutex::new(HashMap::new()),
+            player_numbers: Mutex::new(HashMap::new()),
+            calibrators: Mutex::new(HashMap::new()),
+            device_configs: Mutex::new(HashMap::new()),
+            config: DualSenseConfig::default(),
+            display_names: Mutex::new(HashMap::new()),
+            idle_detectors: Mutex::new(HashMap::new()),
+            poll_limit: Semaphore::new(usize::MAX),
+            connection_preference: ConnectionPreference::default(),
+            skipped_duplicates: Mutex::new(SkippedDuplicates::default()),
+            sinks: Mutex::new(HashMap::new()),
+            lightbar_tasks: Mutex::new(HashMap::new()),
+            charging_statuses: Mutex::new(HashMap::new()),
+            lightbars: Mutex::new(HashMap::new()),
+            dim_idle_lightbar: true,
+            latest_firmware: Mutex::new(None),
+            connection_feedback: None,
+            events: EventEmitter::default(),
         }
     }
 
+    pub fn executor(&self) -> &Arc<Executor<'static>> {
+        &self.executor
+    }
+
+    /// Runs `future` while driving the executor of the manager. Long running loops like
+    /// `watch_pnp` and `watch_status` should be run through it.
+    pub async fn run<T>(&self, future: impl Future<Output = T>) -> T {
+        self.executor.run(future).await
+    }
+
     pub fn set_event_handler<F>(&mut self, handler: F)
     where
         F: Fn(DeviceManagerEvent) + Send + Sync + 'static,
     {
-        self.event_handler = Some(Arc::new(Box::new(handler)));
+        self.events.handler = Some(Arc::new(Box::new(handler)));
+    }
+
+    /// Receives every event from now on, alongside the handler and other subscribers.
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&self) -> Receiver<DeviceManagerEvent> {
+        let (sender, receiver) = channel::unbounded();
+        self.events.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Limits how many devices are polled at once, 1 serializes all polls.
+    pub fn set_poll_concurrency(&mut self, max: usize) {
+        self.poll_limit = Semaphore::new(max.max(1));
+    }
+
+    /// Replaces the config devices are opened with from now on, which is also the
+    /// `device_config` of every device without its own. Devices already open keep the
+    /// retries they were opened with.
+    pub fn apply_config(&mut self, config: DualSenseConfig) {
+        self.config = config;
+    }
+
+    pub fn config(&self) -> &DualSenseConfig {
+        &self.config
     }
 
-    async fn insert_device(&self, device: DualSense) {
+    pub fn set_connection_preference(&mut self, pref: ConnectionPreference) {
+        self.connection_preference = pref;
+    }
+
+    /// Rumbles newly opened controllers briefly, so users can tell they were recognized.
+    /// Disabled by default.
+    pub fn set_connection_feedback(&mut self, rumble_duration_ms: u64, weak: u8, strong: u8) {
+        self.connection_feedback = Some(ConnectionFeedback {
+            duration: Duration::from_millis(rumble_duration_ms),
+            weak,
+            strong,
+        });
+    }
+
+    /// Dims the lightbar of idle controllers to a tenth of its brightness, until they are used
+    /// again. Enabled by default, charging controllers keep their charging lightbar.
+    pub fn set_idle_dimming(&mut self, enabled: bool) {
+        self.dim_idle_lightbar = enabled;
+    }
+
+    /// Finds an open device which is the same physical controller, by serial number
+    async fn find_duplicate(
+        &self,
+        device: &DualSense,
+    ) -> Option<(DeviceId, DualSenseConnectionType)> {
+        let serial = device.serial_number()?;
+        self.opened_devices
+            .lock()
+            .await
+            .iter()
+            .find(|(device_id, opened)| {
+                *device_id != device.device_id()
+                    && opened.serial_number() == Some(serial)
+                    && (self.connection_preference != ConnectionPreference::Independent
+                        || opened.connection_type() == device.connection_type())
+            })
+            .map(|(device_id, opened)| (device_id.clone(), opened.connection_type()))
+    }
+
+    async fn insert_device(&self, mut device: DualSense) {
+        if let Some((existing_id, existing_type)) = self.find_duplicate(&device).await {
+            let replaces = self.skipped_duplicates.lock().await.resolve(
+                self.connection_preference,
+                (device.device_id(), device.connection_type()),
+                (&existing_id, existing_type),
+            );
+            if !replaces {
+                return;
+            }
+            self.close_device(&existing_id).await;
+        }
+
+        let serial = device.serial_number().map(str::to_owned);
+        let display_name = match &serial {
+            Some(serial) => self.display_names.lock().await.get(serial).cloned(),
+            None => None,
+        };
+        if let Some(name) = display_name {
+            device.set_display_name(name);
+        }
+
         let device_id = device.device_id().clone();
-        let device_name = device.name();
+        let device_name = device.display_name().into_owned();
+        let connection_type = device.connection_type();
 
         let device = Arc::new(device);
         self.opened_devices
             .lock()
             .await
             .insert(device_id.clone(), device.clone());
+        self.connected_at
+            .lock()
+            .await
+            .insert(device_id.clone(), Instant::now());
+        let player_number = self.assign_player_number(&device_id).await;
 
-        if let Some(handler) = &self.event_handler {
-            handler(DeviceManagerEvent::Connected(
-                device_id.clone(),
-                device_name,
-            ));
+        if let Some(feedback) = self.connection_feedback {
+            if let Ok(sink) = self.device_sink(&device_id).await {
+                self.spawn_with_panic_log(play_connection_feedback(sink, feedback))
+                    .detach();
+            }
+        }
+
+        if self.events.has_listeners() {
+            self.events.emit(DeviceManagerEvent::Connected {
+                id: device_id.clone(),
+                name: device_name,
+                serial,
+                connection_type,
+            });
+            if let Some(player_number) = player_number {
+                self.events.emit(DeviceManagerEvent::PlayerNumberAssigned(
+                    device_id.clone(),
+                    player_number,
+                ));
+            }
+            self.check_firmware(&device_id, &device).await;
             self.update_device_status(device_id, device).await;
         }
     }
 
     async fn close_device(&self, device_id: &DeviceId) {
-        self.opened_devices.lock().await.remove(device_id);
-
-        if let Some(handler) = &self.event_handler {
-            handler(DeviceManagerEvent::Disconnected(device_id.clone()));
+        if let Some(device) = self.opened_devices.lock().await.remove(device_id) {
+            device.set_lifecycle(DualSenseLifecycle::Disconnected);
         }
+        self.connected_at.lock().await.remove(device_id);
+        self.player_numbers.lock().await.remove(device_id);
+        self.calibrators.lock().await.remove(device_id);
+        self.idle_detectors.lock().await.remove(device_id);
+        self.sinks.lock().await.remove(device_id);
+        self.lightbar_tasks.lock().await.remove(device_id);
+        self.charging_statuses.lock().await.remove(device_id);
+        self.lightbars.lock().await.remove(device_id);
+
+        self.events
+            .emit(DeviceManagerEvent::Disconnected(device_id.clone()));
     }
 
-    async fn open_device_id(&self, device_id: DeviceId) -> HidResult<()> {
-        if self.opened_devices.lock().await.get(&device_id).is_some() {
+    /// Devices are forgotten on disconnect, hidraw nodes get reused by other controllers.
+    /// Drops without a disconnect are recovered by `read_with_reconnect` instead.
+    ///
+    /// A known id is reconnected in place, keeping its uptime and player number.
+    async fn open_device_id(&self, device_id: DeviceId) -> DualSenseResult<()> {
+        let known = self.opened_devices.lock().await.get(&device_id).cloned();
+        if let Some(device) = known {
+            device.reconnect().await?;
             return Ok(());
         }
-        let device = DualSense::open_device_id(&self.hid, &device_id).await?;
+        let device =
+            DualSense::open_device_id_with_config(&self.hid, &device_id, &self.config).await?;
         self.insert_device(device).await;
         Ok(())
     }
 
-    pub async fn open_all_devices(&self) -> HidResult<()> {
+    pub async fn open_all_devices(&self) -> DualSenseResult<()> {
         let devices = DualSense::enumerate(&self.hid)
             .await?
-            .map(|device| smol::spawn(async move { DualSense::open_device(device).await }))
+            .map(|device| {
+                let config = self.config.clone();
+                self.spawn_with_panic_log(async move {
+                    DualSense::open_device_with_config(device, &config).await
+                })
+            })
             .collect::<Vec<_>>()
             .await;
 
         for device in devices {
-            if let Ok(device) = device.await {
+            if let Some(Ok(device)) = self.run(device).await {
                 self.insert_device(device).await;
             }
         }
@@ -87,7 +476,7 @@ impl DeviceManager {
         Ok(())
     }
 
-    pub async fn watch_pnp(&self) -> HidResult<()> {
+    pub async fn watch_pnp(&self) -> DualSenseResult<()> {
         let mut watch_stream = self.hid.watch()?;
         while let Some(event) = watch_stream.next().await {
             match event {
@@ -96,42 +485,200 @@ impl DeviceManager {
                 }
                 DeviceEvent::Disconnected(device_id) => {
                     self.close_device(&device_id).await;
+                    self.reopen_skipped_duplicates(&device_id).await;
                 }
             }
         }
         Ok(())
     }
 
+    /// Opens the connections skipped in favour of `device_id`, which went away. Called on plug
+    /// and play disconnects, which also follow devices already closed after a failed read.
+    async fn reopen_skipped_duplicates(&self, device_id: &DeviceId) {
+        let skipped = self.skipped_duplicates.lock().await.disconnected(device_id);
+        for skipped_id in skipped {
+            if let Err(err) = self.open_device_id(skipped_id).await {
+                tracing::debug!("Failed to reopen a skipped duplicate: {err}");
+            }
+        }
+    }
+
     pub async fn update_device_status(&self, device_id: DeviceId, device: Arc<DualSense>) -> () {
-        if self.event_handler.is_none() {
+        if !self.events.has_listeners() {
             return;
         }
-        let event_handler = self.event_handler.as_ref().unwrap().clone();
-        let result = smol::spawn({
+        let events = self.events.clone();
+        let task = self.spawn_with_panic_log({
             let device_id = device_id.clone();
+            let device = device.clone();
             async move {
                 let mut ds_conn = device.connect().await?;
 
-                let report = ds_conn.read_input_report().await?;
+                let mut report = ds_conn.read_input_report().await?;
+                for _ in 1..STATUS_POLL_REPORTS {
+                    report = ds_conn.read_input_report().await?;
+                }
                 let (capacity, charging) = report.battery();
 
-                event_handler(DeviceManagerEvent::BatteryUpdate(
-                    device_id,
+                events.emit(DeviceManagerEvent::BatteryUpdate(
+                    device_id.clone(),
                     (capacity, charging),
                 ));
+                let stats = ds_conn.stats();
+                events.emit(DeviceManagerEvent::StatsUpdate {
+                    id: device_id,
+                    report_rate_hz: stats.report_rate_hz(),
+                    p95_lag_ms: stats.p95_ms(),
+                });
+
+                Ok::<_, DualSenseError>(report)
+            }
+        });
+        let result = self.run(task).await;
 
-                Ok::<(), HidError>(())
+        match result {
+            Some(Ok(report)) => {
+                self.update_lightbar(&device_id, report.charging_status())
+                    .await;
+                self.update_idle_state(device_id, &report).await
+            }
+            Some(Err(err)) if err.is_disconnected() => {
+                self.close_device(&device_id).await;
+            }
+            Some(Err(_)) | None => {}
+        }
+    }
+
+    /// Spawns `future` on the executor. A panic is logged and emitted as an `Error` event
+    /// instead of silently ending the task, and doesn't trigger the panic reset hook.
+    fn spawn_with_panic_log<F, T>(&self, future: F) -> Task<Option<T>>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let events = self.events.clone();
+        let mut future = Box::pin(future);
+        let future = futures_lite::future::poll_fn(move |cx| {
+            without_reset(|| Future::poll(future.as_mut(), cx))
+        });
+        self.executor.spawn(async move {
+            match AssertUnwindSafe(future).catch_unwind().await {
+                Ok(output) => Some(output),
+                Err(panic) => {
+                    let message = panic_message(&*panic);
+                    tracing::error!("Device manager task panicked: {message}");
+                    events.emit(DeviceManagerEvent::Error(message));
+                    None
+                }
             }
         })
-        .await;
+    }
 
-        if let Err(HidError::Disconnected | HidError::NotConnected) = result {
-            self.close_device(&device_id).await;
+    /// Pulses the lightbar while charging and turns it off once fully charged. When charging
+    /// stops before that, the lightbar last written with `write_output_report` is restored.
+    async fn update_lightbar(&self, device_id: &DeviceId, status: ChargingStatus) {
+        let previous = self
+            .charging_statuses
+            .lock()
+            .await
+            .insert(device_id.clone(), status);
+        if status == ChargingStatus::Charging {
+            {
+                let mut lightbar_tasks = self.lightbar_tasks.lock().await;
+                // the animation ends on its own after a failed write, it is restarted then
+                if lightbar_tasks
+                    .get(device_id)
+                    .is_some_and(|task| !task.is_finished())
+                {
+                    return;
+                }
+                lightbar_tasks.remove(device_id);
+            }
+            // the lock isn't held while connecting, another poll may start the animation meanwhile
+            let Ok(sink) = self.device_sink(device_id).await else {
+                return;
+            };
+            if let Entry::Vacant(entry) = self.lightbar_tasks.lock().await.entry(device_id.clone())
+            {
+                entry.insert(self.spawn_with_panic_log(animate_lightbar(sink, CHARGING_LIGHTBAR)));
+            }
+            return;
+        }
+
+        let animated = self.lightbar_tasks.lock().await.remove(device_id).is_some();
+        let lightbar = match status {
+            ChargingStatus::Full if animated || previous != Some(ChargingStatus::Full) => (0, 0, 0),
+            _ if animated => self.resting_lightbar(device_id).await,
+            _ => return,
+        };
+        self.show_lightbar(device_id, lightbar).await;
+    }
+
+    /// The lightbar shown while not charging: the last one written with `write_output_report`,
+    /// dimmed while the device is idle
+    async fn resting_lightbar(&self, device_id: &DeviceId) -> (u8, u8, u8) {
+        let (red, green, blue) = self
+            .lightbars
+            .lock()
+            .await
+            .get(device_id)
+            .copied()
+            .unwrap_or_else(|| from_preset(LightbarPreset::BluePS, None));
+        let idle = self
+            .idle_detectors
+            .lock()
+            .await
+            .get(device_id)
+            .is_some_and(IdleDetector::is_idle);
+        if !(idle && self.dim_idle_lightbar) {
+            return (red, green, blue);
+        }
+        let dim = |value: u8| (value as u16 * IDLE_LIGHTBAR_PERCENT / 100) as u8;
+        (dim(red), dim(green), dim(blue))
+    }
+
+    /// Not through `write_output_report`, the lightbar to restore stays the same
+    async fn show_lightbar(&self, device_id: &DeviceId, (red, green, blue): (u8, u8, u8)) {
+        let report = DualSenseOutputReportBuilder::new()
+            .lightbar(red, green, blue)
+            .build();
+        if let Ok(sink) = self.device_sink(device_id).await {
+            let _ = sink.write_output_report(report).await;
+        }
+    }
+
+    /// Fed the report of every status poll, see `IdleDetector` for what it misses in between.
+    /// Dims the lightbar on idle and restores it on activity, unless the charging status
+    /// decides the lightbar.
+    async fn update_idle_state(&self, device_id: DeviceId, report: &DualSenseInputReport) {
+        let (was_idle, became_idle, is_idle) = {
+            let mut idle_detectors = self.idle_detectors.lock().await;
+            let detector = idle_detectors.entry(device_id.clone()).or_default();
+            let was_idle = detector.is_idle();
+            let became_idle = detector.update(report, Instant::now());
+            (was_idle, became_idle, detector.is_idle())
+        };
+
+        let became_active = was_idle && !is_idle;
+        let charging = matches!(
+            self.charging_statuses.lock().await.get(&device_id),
+            Some(ChargingStatus::Charging | ChargingStatus::Full)
+        );
+        if (became_idle || became_active) && self.dim_idle_lightbar && !charging {
+            let lightbar = self.resting_lightbar(&device_id).await;
+            self.show_lightbar(&device_id, lightbar).await;
+        }
+
+        if became_idle {
+            self.events.emit(DeviceManagerEvent::DeviceIdle(device_id));
+        } else if became_active {
+            self.events
+                .emit(DeviceManagerEvent::DeviceActive(device_id));
         }
     }
 
     pub async fn update_status(&self) -> () {
-        if self.event_handler.is_none() {
+        if !self.events.has_listeners() {
             return;
         }
         // clone the hashmap to avoid holding the lock while emitting events
@@ -140,8 +687,667 @@ impl DeviceManager {
             self.update_device_status(device_id.clone(), device.clone())
         });
 
+        self.join_polls(tasks).await;
+    }
+
+    /// Runs per-device polls concurrently, as many at once as `set_poll_concurrency` allows
+    async fn join_polls<'a, F>(&'a self, polls: impl IntoIterator<Item = F>) -> Vec<F::Output>
+    where
+        F: Future + 'a,
+        F::Output: 'a,
+    {
+        join_limited(&self.poll_limit, polls).await
+    }
+
+    /// Polls the status of every device periodically, idle detection depends on it. Every
+    /// `HEALTH_CHECK_INTERVAL` the devices are health checked as well.
+    pub async fn watch_status(&self, interval: Duration) -> () {
+        let mut last_health_check = Instant::now();
+        loop {
+            Timer::after(interval).await;
+            self.update_status().await;
+            if last_health_check.elapsed() >= HEALTH_CHECK_INTERVAL {
+                last_health_check = Instant::now();
+                self.check_health().await;
+            }
+        }
+    }
+
+    /// Emits `HealthCheckFailed` for every device that doesn't answer properly
+    pub async fn check_health(&self) -> () {
+        let devices = self.opened_devices.lock().await.clone();
+        let checks = devices.into_iter().map(|(device_id, device)| async move {
+            match device.health_check().await {
+                Ok(true) => {}
+                Ok(false) | Err(_) => self
+                    .events
+                    .emit(DeviceManagerEvent::HealthCheckFailed(device_id)),
+            }
+        });
+        self.join_polls(checks).await;
+    }
+
+    /// Replaces the connection output reports are written to, e.g. with a `RecordingSink` in tests
+    pub async fn set_device_sink(&self, device_id: DeviceId, sink: Box<dyn DualSenseSink>) {
+        self.sinks.lock().await.insert(device_id, Arc::from(sink));
+    }
+
+    pub async fn write_output_report(
+        &self,
+        device_id: &DeviceId,
+        report: DualSenseOutputReport,
+    ) -> DualSenseResult<()> {
+        let lightbar = report
+            .features()
+            .contains(OutputFeatures::LIGHTBAR)
+            .then(|| report.lightbar());
+        self.device_sink(device_id)
+            .await?
+            .write_output_report(report)
+            .await?;
+        if let Some(lightbar) = lightbar {
+            self.lightbars
+                .lock()
+                .await
+                .insert(device_id.clone(), lightbar);
+        }
+        Ok(())
+    }
+
+    /// The sink set for the device, or a new connection to it
+    async fn device_sink(&self, device_id: &DeviceId) -> DualSenseResult<Arc<dyn DualSenseSink>> {
+        if let Some(sink) = self.sinks.lock().await.get(device_id).cloned() {
+            return Ok(sink);
+        }
+        let device = self
+            .opened_devices
+            .lock()
+            .await
+            .get(device_id)
+            .cloned()
+            .ok_or(HidError::NotConnected)?;
+        Ok(Arc::new(device.connect().await?))
+    }
+
+    /// Resets rumble, lights and trigger effects of every device, then closes them.
+    pub async fn shutdown(&self) -> () {
+        for device_id in self.reset_all_devices().await {
+            self.close_device(&device_id).await;
+        }
+    }
+
+    /// Writes a reset report to every open device, returning their ids
+    async fn reset_all_devices(&self) -> Vec<DeviceId> {
+        let device_ids = self
+            .opened_devices
+            .lock()
+            .await
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        for device_id in &device_ids {
+            let report = DualSenseOutputReport::reset_all_features();
+            if let Err(err) = self.write_output_report(device_id, report).await {
+                tracing::debug!("Failed to reset {device_id:?}: {err}");
+            }
+        }
+        device_ids
+    }
+
+    pub async fn opened_devices(&self) -> Vec<(DeviceId, Arc<DualSense>)> {
+        self.opened_devices
+            .lock()
+            .await
+            .iter()
+            .map(|(device_id, device)| (device_id.clone(), device.clone()))
+            .collect()
+    }
+
+    /// Compares the firmware of every device with `latest`, now and as they connect. Setting
+    /// the same version again doesn't repeat the `FirmwareUpdateAvailable` events.
+    pub async fn set_latest_firmware(&self, latest: FirmwareVersion) {
+        if self.latest_firmware.lock().await.replace(latest) == Some(latest) {
+            return;
+        }
+        for (device_id, device) in self.opened_devices().await {
+            self.check_firmware(&device_id, &device).await;
+        }
+    }
+
+    pub async fn latest_firmware(&self) -> Option<FirmwareVersion> {
+        *self.latest_firmware.lock().await
+    }
+
+    /// Emits `FirmwareUpdateAvailable` if the device runs an older firmware than the latest
+    /// known one. Devices whose firmware info couldn't be read are skipped.
+    async fn check_firmware(&self, device_id: &DeviceId, device: &DualSense) {
+        let Some(latest) = self.latest_firmware().await else {
+            return;
+        };
+        let Some(current) = device
+            .device_info()
+            .map(DeviceInfoReport::installed_firmware)
+        else {
+            return;
+        };
+        if let Some(latest) = check_firmware_update(&current, &latest) {
+            self.events
+                .emit(DeviceManagerEvent::FirmwareUpdateAvailable(
+                    device_id.clone(),
+                    latest,
+                ));
+        }
+    }
+
+    /// Time since the device was opened, reconnecting a known device doesn't reset it
+    pub async fn device_uptime(&self, device_id: &DeviceId) -> Option<Duration> {
+        let connected_at = *self.connected_at.lock().await.get(device_id)?;
+        Some(connected_at.elapsed())
+    }
+
+    pub async fn player_number(&self, device_id: &DeviceId) -> Option<u8> {
+        self.player_numbers.lock().await.get(device_id).copied()
+    }
+
+    /// The lowest player number not taken by another device, so a controller that reconnects
+    /// gets its old number back as long as nobody else took it. `None` once all of the
+    /// `MAX_PLAYERS` numbers are taken, the device then goes without one until it reconnects.
+    async fn assign_player_number(&self, device_id: &DeviceId) -> Option<u8> {
+        let mut player_numbers = self.player_numbers.lock().await;
+        if let Some(number) = player_numbers.get(device_id) {
+            return Some(*number);
+        }
+        let number = (1..=MAX_PLAYERS)
+            .find(|number| !player_numbers.values().any(|taken| taken == number))?;
+        player_numbers.insert(device_id.clone(), number);
+        Some(number)
+    }
+
+    /// Names the controller with `serial` in `Connected` events from its next connection on
+    pub async fn set_display_name(&self, serial: String, name: String) {
+        self.display_names.lock().await.insert(serial, name);
+    }
+
+    pub async fn device_config(&self, device_id: &DeviceId) -> DualSenseConfig {
+        self.device_configs
+            .lock()
+            .await
+            .get(device_id)
+            .cloned()
+            .unwrap_or_else(|| self.config.clone())
+    }
+
+    pub async fn set_device_config(&self, device_id: DeviceId, config: DualSenseConfig) {
+        self.device_configs.lock().await.insert(device_id, config);
+    }
+
+    pub async fn stick_calibrator(&self, device_id: &DeviceId) -> Option<StickCalibrator> {
+        self.calibrators.lock().await.get(device_id).cloned()
+    }
+
+    /// Samples the resting stick position of every device, they must not be touched meanwhile.
+    pub async fn recalibrate_sticks(&self) -> () {
+        let devices = self.opened_devices.lock().await.clone();
+        for (device_id, device) in devices {
+            let mut calibrator = self
+                .calibrators
+                .lock()
+                .await
+                .get(&device_id)
+                .cloned()
+                .unwrap_or_default();
+            calibrator.recalibrate();
+
+            let result = async {
+                let mut ds_conn = device.connect().await?;
+                while !calibrator.is_calibrated() {
+                    let report = ds_conn.read_input_report().await?;
+                    calibrator.update(&report);
+                }
+                let stats = ds_conn.stats();
+                Ok::<_, DualSenseError>((stats.report_rate_hz(), stats.p95_ms()))
+            }
+            .await;
+
+            match result {
+                Ok((report_rate_hz, p95_lag_ms)) => {
+                    self.calibrators
+                        .lock()
+                        .await
+                        .insert(device_id.clone(), calibrator);
+                    self.events.emit(DeviceManagerEvent::StatsUpdate {
+                        id: device_id,
+                        report_rate_hz,
+                        p95_lag_ms,
+                    });
+                }
+                Err(err) if err.is_disconnected() => {
+                    self.close_device(&device_id).await;
+                }
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+/// Resets the controllers still open, like `shutdown` but without emitting `Disconnected`.
+///
+/// This is best effort: the reports are written from a separate thread, since the executor
+/// the manager was used from may be gone already, and only for up to a second. Nothing is
+/// reset when the process is killed, e.g. with SIGKILL.
+#[cfg(feature = "reset_on_drop")]
+impl Drop for DeviceManager {
+    fn drop(&mut self) {
+        let device_manager = &*self;
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                smol::block_on(
+                    async {
+                        device_manager.reset_all_devices().await;
+                    }
+                    .or(async {
+                        Timer::after(DROP_RESET_TIMEOUT).await;
+                    }),
+                )
+            });
+        });
+    }
+}
+
+async fn play_connection_feedback(sink: Arc<dyn DualSenseSink>, feedback: ConnectionFeedback) {
+    let rumble = DualSenseOutputReportBuilder::new()
+        .rumble(feedback.strong, feedback.weak)
+        .build();
+    if sink.write_output_report(rumble).await.is_err() {
+        return;
+    }
+    Timer::after(feedback.duration).await;
+    let stop = DualSenseOutputReportBuilder::new().rumble(0, 0).build();
+    let _ = sink.write_output_report(stop).await;
+}
+
+/// Writes the frames of `effect` until a write fails, e.g. because the device disconnected.
+async fn animate_lightbar(sink: Arc<dyn DualSenseSink>, effect: LightbarEffect) {
+    let start = Instant::now();
+    loop {
+        let (red, green, blue) = effect.color_at(start.elapsed());
+        let report = DualSenseOutputReportBuilder::new()
+            .lightbar(red, green, blue)
+            .build();
+        if sink.write_output_report(report).await.is_err() {
+            return;
+        }
+        Timer::after(LIGHTBAR_FRAME_INTERVAL).await;
+    }
+}
+
+/// Runs the futures concurrently, with at most as many in flight as permits in `limit`.
+async fn join_limited<'a, F>(
+    limit: &'a Semaphore,
+    futures: impl IntoIterator<Item = F>,
+) -> Vec<F::Output>
+where
+    F: Future + 'a,
+    F::Output: 'a,
+{
+    let ex = LocalExecutor::new();
+    let tasks = futures
+        .into_iter()
+        .map(|future| {
+            ex.spawn(async move {
+                let _permit = limit.acquire().await;
+                future.await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    ex.run(async move {
+        let mut outputs = Vec::with_capacity(tasks.len());
         for task in tasks {
-            let _ = task.await;
+            outputs.push(task.await);
         }
+        outputs
+    })
+    .await
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures_lite::future;
+    use macro_rules_attribute::apply;
+    use smol_macros::test;
+
+    use super::*;
+    use crate::dualsense::{
+        proto::{Button, DualSenseInputReportBuilder},
+        sink::RecordingSink,
+    };
+
+    async fn max_concurrent_polls(device_manager: &DeviceManager, devices: usize) -> usize {
+        let running = &AtomicUsize::new(0);
+        let max_running = &AtomicUsize::new(0);
+
+        let polls = (0..devices).map(move |_| async move {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now, Ordering::SeqCst);
+            Timer::after(Duration::from_millis(10)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+        });
+        device_manager.join_polls(polls).await;
+
+        max_running.load(Ordering::SeqCst)
+    }
+
+    #[apply(test!)]
+    async fn poll_concurrency_serializes() {
+        let mut device_manager = DeviceManager::new();
+        device_manager.set_poll_concurrency(1);
+        assert_eq!(max_concurrent_polls(&device_manager, 3).await, 1);
+        // 0 would never poll
+        device_manager.set_poll_concurrency(0);
+        assert_eq!(max_concurrent_polls(&device_manager, 3).await, 1);
+    }
+
+    #[apply(test!)]
+    async fn poll_concurrency_limited() {
+        let mut device_manager = DeviceManager::new();
+        device_manager.set_poll_concurrency(2);
+        assert_eq!(max_concurrent_polls(&device_manager, 5).await, 2);
+    }
+
+    #[apply(test!)]
+    async fn poll_concurrency_unlimited() {
+        assert_eq!(max_concurrent_polls(&DeviceManager::new(), 3).await, 3);
+    }
+
+    #[apply(test!)]
+    async fn panicking_task_emits_error() {
+        let device_manager = DeviceManager::new();
+        let events = device_manager.subscribe();
+
+        let task = device_manager.spawn_with_panic_log(async { panic!("boom") });
+        assert_eq!(device_manager.run(task).await, None::<()>);
+        assert!(matches!(
+            events.recv().await,
+            Ok(DeviceManagerEvent::Error(message)) if message == "boom"
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[apply(test!)]
+    async fn subscribers_receive_events() {
+        let device_manager = DeviceManager::new();
+        let first = device_manager.subscribe();
+        let dropped = device_manager.subscribe();
+        drop(dropped);
+
+        let id = DeviceId::DevPath("/dev/hidraw0".into());
+        device_manager
+            .events
+            .emit(DeviceManagerEvent::Disconnected(id.clone()));
+
+        assert!(matches!(
+            first.recv().await,
+            Ok(DeviceManagerEvent::Disconnected(disconnected)) if disconnected == id
+        ));
+        assert_eq!(device_manager.events.subscribers.lock().unwrap().len(), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[apply(test!)]
+    async fn with_config_is_the_default_device_config() {
+        let config = DualSenseConfig {
+            deadzone: 0.25,
+            open_max_retries: 10,
+            ..Default::default()
+        };
+        let device_manager = DeviceManager::with_config(config.clone());
+        assert_eq!(device_manager.config(), &config);
+
+        let id = DeviceId::DevPath("/dev/hidraw0".into());
+        assert_eq!(device_manager.device_config(&id).await, config);
+
+        let own = DualSenseConfig::default();
+        device_manager
+            .set_device_config(id.clone(), own.clone())
+            .await;
+        assert_eq!(device_manager.device_config(&id).await, own);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn skipped_duplicate_reopens_after_kept_disconnects() {
+        let usb = DeviceId::DevPath("/dev/hidraw0".into());
+        let bt = DeviceId::DevPath("/dev/hidraw1".into());
+        for (preference, kept, skipped) in [
+            (ConnectionPreference::PreferUSB, &usb, &bt),
+            (ConnectionPreference::PreferBT, &bt, &usb),
+            (ConnectionPreference::FirstSeen, &bt, &usb),
+        ] {
+            // connected over BT first, then the cable is plugged in
+            let mut duplicates = SkippedDuplicates::default();
+            let replaces = duplicates.resolve(
+                preference,
+                (&usb, DualSenseConnectionType::USB),
+                (&bt, DualSenseConnectionType::BT),
+            );
+            assert_eq!(replaces, kept == &usb, "{preference:?}");
+            assert_eq!(duplicates.disconnected(kept), [skipped.clone()]);
+            assert!(duplicates.disconnected(kept).is_empty());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn skipped_duplicate_forgotten_when_it_disconnects() {
+        let usb = DeviceId::DevPath("/dev/hidraw0".into());
+        let bt = DeviceId::DevPath("/dev/hidraw1".into());
+        for preference in [
+            ConnectionPreference::PreferUSB,
+            ConnectionPreference::PreferBT,
+        ] {
+            let mut duplicates = SkippedDuplicates::default();
+            let replaces = duplicates.resolve(
+                preference,
+                (&usb, DualSenseConnectionType::USB),
+                (&bt, DualSenseConnectionType::BT),
+            );
+            let (kept, skipped) = if replaces { (&usb, &bt) } else { (&bt, &usb) };
+            assert!(duplicates.disconnected(skipped).is_empty());
+            assert!(duplicates.disconnected(kept).is_empty());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[apply(test!)]
+    async fn idle_lightbar_is_dimmed() {
+        let device_manager = DeviceManager::new();
+        let id = DeviceId::DevPath("/dev/hidraw0".into());
+        let sink = Arc::new(RecordingSink::new());
+        device_manager
+            .set_device_sink(id.clone(), Box::new(sink.clone()))
+            .await;
+        let lightbar = DualSenseOutputReportBuilder::new()
+            .lightbar(0, 100, 250)
+            .build();
+        device_manager
+            .write_output_report(&id, lightbar)
+            .await
+            .unwrap();
+        let last_lightbar = || sink.reports().last().map(DualSenseOutputReport::lightbar);
+        device_manager
+            .idle_detectors
+            .lock()
+            .await
+            .insert(id.clone(), IdleDetector::new(Duration::ZERO));
+        let resting = DualSenseInputReportBuilder::new().build();
+        let pressed = DualSenseInputReportBuilder::new()
+            .button(Button::Cross, true)
+            .build();
+
+        device_manager.update_idle_state(id.clone(), &resting).await;
+        assert_eq!(last_lightbar(), Some((0, 10, 25)));
+        device_manager.update_idle_state(id.clone(), &pressed).await;
+        assert_eq!(last_lightbar(), Some((0, 100, 250)));
+        assert_eq!(sink.reports().len(), 3);
+
+        // the charging animation decides while charging
+        device_manager
+            .charging_statuses
+            .lock()
+            .await
+            .insert(id.clone(), ChargingStatus::Charging);
+        device_manager.update_idle_state(id.clone(), &resting).await;
+        assert_eq!(sink.reports().len(), 3);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[apply(test!)]
+    async fn lightbar_follows_charging_status() {
+        let device_manager = DeviceManager::new();
+        let id = DeviceId::DevPath("/dev/hidraw0".into());
+        let sink = Arc::new(RecordingSink::new());
+        device_manager
+            .set_device_sink(id.clone(), Box::new(sink.clone()))
+            .await;
+        let blue = DualSenseOutputReportBuilder::new()
+            .lightbar(0, 0, 255)
+            .build();
+        device_manager.write_output_report(&id, blue).await.unwrap();
+        let last_lightbar = || sink.reports().last().map(DualSenseOutputReport::lightbar);
+
+        // unplugged before full, the previous color comes back
+        device_manager
+            .update_lightbar(&id, ChargingStatus::Charging)
+            .await;
+        assert!(device_manager.lightbar_tasks.lock().await.contains_key(&id));
+        device_manager
+            .update_lightbar(&id, ChargingStatus::Discharging)
+            .await;
+        assert!(device_manager.lightbar_tasks.lock().await.is_empty());
+        assert_eq!(last_lightbar(), Some((0, 0, 255)));
+        device_manager
+            .update_lightbar(&id, ChargingStatus::Discharging)
+            .await;
+        assert_eq!(sink.reports().len(), 2);
+
+        // an animation that ended is restarted
+        let ended = device_manager.spawn_with_panic_log(async {});
+        while !ended.is_finished() {
+            device_manager.run(future::yield_now()).await;
+        }
+        device_manager
+            .lightbar_tasks
+            .lock()
+            .await
+            .insert(id.clone(), ended);
+        device_manager
+            .update_lightbar(&id, ChargingStatus::Charging)
+            .await;
+        assert!(!device_manager.lightbar_tasks.lock().await[&id].is_finished());
+
+        // off once full, only once
+        device_manager
+            .update_lightbar(&id, ChargingStatus::Full)
+            .await;
+        assert_eq!(last_lightbar(), Some((0, 0, 0)));
+        let written = sink.reports().len();
+        device_manager
+            .update_lightbar(&id, ChargingStatus::Full)
+            .await;
+        assert_eq!(sink.reports().len(), written);
+    }
+
+    #[apply(test!)]
+    async fn latest_firmware_without_devices() {
+        let device_manager = DeviceManager::new();
+        let events = device_manager.subscribe();
+        assert_eq!(device_manager.latest_firmware().await, None);
+
+        device_manager
+            .set_latest_firmware(FirmwareVersion(0x0224))
+            .await;
+        assert_eq!(
+            device_manager.latest_firmware().await,
+            Some(FirmwareVersion(0x0224))
+        );
+        assert!(events.try_recv().is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[apply(test!)]
+    async fn device_uptime_increases() {
+        let device_manager = DeviceManager::new();
+        let id = DeviceId::DevPath("/dev/hidraw0".into());
+        assert_eq!(device_manager.device_uptime(&id).await, None);
+
+        device_manager
+            .connected_at
+            .lock()
+            .await
+            .insert(id.clone(), Instant::now() - Duration::from_secs(5));
+        let first = device_manager.device_uptime(&id).await.unwrap();
+        Timer::after(Duration::from_millis(10)).await;
+        let second = device_manager.device_uptime(&id).await.unwrap();
+        assert!(first >= Duration::from_secs(5));
+        assert!(second > first);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[apply(test!)]
+    async fn reconnect_reuses_freed_player_number() {
+        let device_manager = DeviceManager::new();
+        let ids = (0..3)
+            .map(|i| DeviceId::DevPath(format!("/dev/hidraw{i}").into()))
+            .collect::<Vec<_>>();
+        for (id, expected) in ids.iter().zip(1..) {
+            assert_eq!(
+                device_manager.assign_player_number(id).await,
+                Some(expected)
+            );
+        }
+
+        device_manager.close_device(&ids[1]).await;
+        assert_eq!(device_manager.player_number(&ids[1]).await, None);
+
+        let reconnected = DeviceId::DevPath("/dev/hidraw3".into());
+        assert_eq!(
+            device_manager.assign_player_number(&reconnected).await,
+            Some(2)
+        );
+        assert_eq!(device_manager.assign_player_number(&ids[2]).await, Some(3));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[apply(test!)]
+    async fn player_numbers_stop_at_max_players() {
+        let device_manager = DeviceManager::new();
+        let ids = (0..=MAX_PLAYERS)
+            .map(|i| DeviceId::DevPath(format!("/dev/hidraw{i}").into()))
+            .collect::<Vec<_>>();
+        for (id, expected) in ids.iter().zip(1..=MAX_PLAYERS) {
+            assert_eq!(
+                device_manager.assign_player_number(id).await,
+                Some(expected)
+            );
+        }
+        let extra = &ids[MAX_PLAYERS as usize];
+        assert_eq!(device_manager.assign_player_number(extra).await, None);
+        assert_eq!(device_manager.player_number(extra).await, None);
+
+        device_manager.close_device(&ids[0]).await;
+        assert_eq!(device_manager.assign_player_number(extra).await, Some(1));
     }
 }
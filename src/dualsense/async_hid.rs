@@ -1,31 +1,67 @@
-use std::time::Duration;
+use std::{
+    borrow::Cow,
+    future::Future,
+    sync::atomic::{AtomicU8, Ordering},
+    time::{Duration, Instant},
+};
 
-use async_hid::{AsyncHidRead, Device, DeviceId, DeviceReader, HidBackend, HidError, HidResult};
+use async_hid::{
+    AsyncHidRead, AsyncHidWrite, Device, DeviceId, DeviceReader, DeviceWriter, HidBackend, HidError,
+};
 use async_io::Timer;
 use futures_lite::{FutureExt, Stream, StreamExt};
+use smol::{
+    Task,
+    channel::{self, Receiver},
+    lock::Mutex,
+};
 use zerocopy::transmute;
 
-use crate::dualsense::proto::DS_FEATURE_REPORT_BT_FULL;
+#[cfg(target_os = "linux")]
+use crate::dualsense::descriptor::parse_hid_descriptor;
+use crate::dualsense::{
+    config::DualSenseConfig,
+    error::{DualSenseError, DualSenseResult},
+    proto::{
+        DS_FEATURE_REPORT_BT_FULL, DS_FEATURE_REPORT_BT_FULL_SIZE, DS_INPUT_CRC32_SEED, ds_crc32,
+    },
+    stats::DualSenseConnectionStats,
+};
 
 use super::proto::{
-    DS_INPUT_REPORT_BT_SIZE, DS_INPUT_REPORT_USB_SIZE, DUALSENSE_PID, DualSenseInputReport,
-    DualSenseInputReportBT, DualSenseInputReportUSB, SONY_VID,
+    DS_FEATURE_REPORT_FIRMWARE_INFO, DS_FEATURE_REPORT_FIRMWARE_INFO_SIZE,
+    DS_FEATURE_REPORT_PAIRING_INFO, DS_FEATURE_REPORT_PAIRING_INFO_SIZE, DS_INPUT_REPORT_BT,
+    DS_INPUT_REPORT_BT_NONFULL_SIZE, DS_INPUT_REPORT_BT_SIZE, DS_INPUT_REPORT_USB,
+    DS_INPUT_REPORT_USB_SIZE, DS_OUTPUT_REPORT_BT, DS_OUTPUT_REPORT_USB, DeviceInfoReport,
+    DualSenseInputReport, DualSenseInputReportBT, DualSenseInputReportUSB, DualSenseModel,
+    DualSenseOutputReport, ImuCalibration, PairingInfo, is_dualsense,
 };
 
 const OPEN_TIMEOUT: u64 = 500;
 const READ_TIMEOUT: u64 = 200;
 const WRITE_TIMEOUT: u64 = 200;
+const RETRY_DELAY: u64 = 100;
+const MAX_RECONNECT_ATTEMPTS: u32 = 3;
+const MAX_BT_FULL_REPORT_ATTEMPTS: u32 = 3;
+const FULL_REPORT_RATE_HZ: u32 = 250;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg(target_os = "linux")]
+const BUS_USB: u16 = 0x03;
+#[cfg(target_os = "linux")]
+const BUS_BLUETOOTH: u16 = 0x05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum DualSenseConnectionType {
     USB,
     BT,
 }
 
 impl DualSenseConnectionType {
+    /// Bluetooth controllers start out sending reduced reports, until the full report mode
+    /// is enabled after opening
     pub fn from_report_size(size: usize) -> Option<Self> {
         match size {
-            DS_INPUT_REPORT_BT_SIZE => Some(Self::BT),
+            DS_INPUT_REPORT_BT_SIZE | DS_INPUT_REPORT_BT_NONFULL_SIZE => Some(Self::BT),
             DS_INPUT_REPORT_USB_SIZE => Some(Self::USB),
             _ => None,
         }
@@ -37,6 +73,31 @@ impl DualSenseConnectionType {
             Self::BT => DS_INPUT_REPORT_BT_SIZE,
         }
     }
+
+    /// Id of the input reports read in this mode, it is also the first byte covered by the
+    /// CRC of Bluetooth reports
+    pub fn report_id(&self) -> u8 {
+        match self {
+            Self::USB => DS_INPUT_REPORT_USB,
+            Self::BT => DS_INPUT_REPORT_BT,
+        }
+    }
+
+    /// Id that has to be prepended to output reports sent over this connection
+    pub fn output_report_id(&self) -> u8 {
+        match self {
+            Self::USB => DS_OUTPUT_REPORT_USB,
+            Self::BT => DS_OUTPUT_REPORT_BT,
+        }
+    }
+
+    /// Expected input report rate. Bluetooth connections are only opened once the full
+    /// report mode is confirmed, which reports as often as USB.
+    pub fn report_rate_hint(&self) -> u32 {
+        match self {
+            Self::USB | Self::BT => FULL_REPORT_RATE_HZ,
+        }
+    }
 }
 
 impl std::fmt::Display for DualSenseConnectionType {
@@ -48,117 +109,638 @@ impl std::fmt::Display for DualSenseConnectionType {
     }
 }
 
+/// Connection state of a `DualSense`, as last observed by the reads and reconnects going
+/// through it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DualSenseLifecycle {
+    Disconnected,
+    /// Reopening after the device came back, e.g. in Bluetooth range again
+    Opening,
+    Connected {
+        since: Instant,
+    },
+    /// `attempt` out of `MAX_RECONNECT_ATTEMPTS`, see `read_with_reconnect`
+    Reconnecting {
+        attempt: u32,
+    },
+}
+
+impl std::fmt::Display for DualSenseLifecycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Disconnected => write!(f, "Disconnected"),
+            Self::Opening => write!(f, "Opening"),
+            Self::Connected { .. } => write!(f, "Connected"),
+            Self::Reconnecting { attempt } => {
+                write!(
+                    f,
+                    "Reconnecting (attempt {attempt}/{MAX_RECONNECT_ATTEMPTS})"
+                )
+            }
+        }
+    }
+}
+
+/// What is known about a controller without opening it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DualSenseInfo {
+    pub device_id: DeviceId,
+    /// Only known where the HID descriptor is readable without opening the device (Linux)
+    pub connection_type: Option<DualSenseConnectionType>,
+    pub serial: Option<String>,
+    pub model: DualSenseModel,
+}
+
 pub struct DualSense {
     device: Device,
     connection_type: DualSenseConnectionType,
+    device_info: Option<DeviceInfoReport>,
+    display_name: Option<String>,
+    lifecycle: std::sync::Mutex<DualSenseLifecycle>,
 }
 
 impl DualSense {
     pub fn is(device: &Device) -> bool {
-        device.vendor_id == SONY_VID && device.product_id == DUALSENSE_PID
+        is_dualsense(device.vendor_id, device.product_id)
     }
 
-    pub async fn enumerate<'a>(hid: &'a HidBackend) -> HidResult<impl Stream<Item = Device> + 'a> {
+    /// Lists the connected DualSense controllers, without opening them.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use async_hid::HidBackend;
+    /// # use dualsense_tray::dualsense::{async_hid::DualSense, error::DualSenseResult};
+    /// # use futures_lite::StreamExt;
+    /// # fn main() -> DualSenseResult<()> { smol::block_on(async {
+    /// let hid = HidBackend::default();
+    /// let mut devices = DualSense::enumerate(&hid).await?;
+    /// while let Some(device) = devices.next().await {
+    ///     println!("{}", device.name);
+    /// }
+    /// # Ok(()) }) }
+    /// ```
+    pub async fn enumerate<'a>(
+        hid: &'a HidBackend,
+    ) -> DualSenseResult<impl Stream<Item = Device> + 'a> {
         let stream = hid.enumerate().await?.filter(DualSense::is);
         Ok(stream)
     }
 
-    pub async fn open_device_id(hid: &HidBackend, device_id: &DeviceId) -> HidResult<Self> {
+    /// Describes every connected controller without opening them, e.g. for a device picker.
+    pub async fn list_all(hid: &HidBackend) -> DualSenseResult<Vec<DualSenseInfo>> {
+        let devices = Self::enumerate(hid).await?.collect::<Vec<_>>().await;
+        Ok(devices
+            .into_iter()
+            .filter_map(|device| {
+                Some(DualSenseInfo {
+                    connection_type: detect_connection_from_descriptor(&device),
+                    serial: device
+                        .serial_number
+                        .clone()
+                        .filter(|serial| !serial.is_empty()),
+                    model: DualSenseModel::from_product_id(device.product_id)?,
+                    device_id: device.id,
+                })
+            })
+            .collect())
+    }
+
+    /// Opens the hidraw node at `path`, e.g. `/dev/hidraw0`, without enumerating every device.
+    ///
+    /// Other platforms identify devices by instance paths or registry ids that can't be
+    /// known in advance, use `enumerate` there.
+    #[cfg(target_os = "linux")]
+    pub async fn open_with_path(hid: &HidBackend, path: &str) -> DualSenseResult<Self> {
+        Self::open_device_id(hid, &DeviceId::DevPath(path.into())).await
+    }
+
+    pub async fn open_device_id(hid: &HidBackend, device_id: &DeviceId) -> DualSenseResult<Self> {
+        Self::open_device_id_with_config(hid, device_id, &DualSenseConfig::default()).await
+    }
+
+    pub async fn open_device_id_with_config(
+        hid: &HidBackend,
+        device_id: &DeviceId,
+        config: &DualSenseConfig,
+    ) -> DualSenseResult<Self> {
         let devices = hid.query_devices(device_id).await?;
         let device = devices
             .into_iter()
             .find(DualSense::is)
             .ok_or(HidError::NotConnected)?;
-        Self::open_device(device).await
+        Self::open_device_with_config(device, config).await
     }
 
-    pub async fn open_device(device: Device) -> HidResult<Self> {
-        let mut reader = device
-            .open_readable()
-            .or(async {
-                Timer::after(Duration::from_millis(OPEN_TIMEOUT)).await;
-                Err(HidError::NotConnected)
-            })
-            .await?;
-
-        let mut buf = [0u8; DS_INPUT_REPORT_BT_SIZE];
-        let size = reader
-            .read_input_report(&mut buf)
-            .or(async {
-                Timer::after(Duration::from_millis(READ_TIMEOUT)).await;
-                Err(HidError::Disconnected)
-            })
-            .await?;
-
-        let connection_type = DualSenseConnectionType::from_report_size(size)
-            .ok_or_else(|| HidError::message("Unknown report size"))?;
+    /// Detects how the device is connected and prepares it for reading.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use async_hid::HidBackend;
+    /// # use dualsense_tray::dualsense::{async_hid::DualSense, error::DualSenseResult};
+    /// # use futures_lite::StreamExt;
+    /// # fn main() -> DualSenseResult<()> { smol::block_on(async {
+    /// let hid = HidBackend::default();
+    /// if let Some(device) = DualSense::enumerate(&hid).await?.next().await {
+    ///     let ds = DualSense::open_device(device).await?;
+    ///     println!("{} over {}", ds.name(), ds.connection_type());
+    /// }
+    /// # Ok(()) }) }
+    /// ```
+    pub async fn open_device(device: Device) -> DualSenseResult<Self> {
+        Self::open_device_with_config(device, &DualSenseConfig::default()).await
+    }
 
-        // Enable full report over Bluetooth
-        if connection_type == DualSenseConnectionType::BT {
-            let mut buf = [0u8; 41];
-            buf[0] = DS_FEATURE_REPORT_BT_FULL;
-            let _ = device.read_feature_report(&mut buf).await;
+    pub async fn open_device_with_config(
+        device: Device,
+        config: &DualSenseConfig,
+    ) -> DualSenseResult<Self> {
+        if !Self::is(&device) {
+            return Err(DualSenseError::UnsupportedDevice(device.product_id));
         }
+        let connection_type = match detect_connection_from_descriptor(&device) {
+            Some(connection_type) => connection_type,
+            // a device that was just plugged in may not send reports right away
+            None => {
+                retry(
+                    config.open_max_retries,
+                    Duration::from_millis(RETRY_DELAY),
+                    || detect_connection_from_report(&device),
+                )
+                .await?
+            }
+        };
 
-        Ok(Self {
+        let device_info = match read_device_info_report(&device).await {
+            Ok(device_info) => Some(device_info),
+            Err(err) => {
+                tracing::debug!("Failed to read the firmware info: {err}");
+                None
+            }
+        };
+        let ds = Self {
             device,
             connection_type,
-        })
+            device_info,
+            display_name: None,
+            lifecycle: std::sync::Mutex::new(DualSenseLifecycle::Opening),
+        };
+        ds.enable_bt_full_report().await?;
+        ds.set_lifecycle(DualSenseLifecycle::Connected {
+            since: Instant::now(),
+        });
+        tracing::info!(
+            "Opened {} at {}",
+            ds.name(),
+            ds.device_path()
+                .map(str::to_owned)
+                .unwrap_or_else(|| format!("{:?}", ds.device_id()))
+        );
+        Ok(ds)
     }
 
-    pub async fn connect(&self) -> HidResult<DualSenseConnection> {
-        let reader = self
-            .device
-            .open_readable()
-            .or(async {
-                Timer::after(Duration::from_millis(OPEN_TIMEOUT)).await;
-                Err(HidError::NotConnected)
-            })
-            .await?;
+    /// A controller that comes back in range starts over in the reduced Bluetooth report
+    /// mode, so unlike `connect` this enables the full report again first.
+    pub async fn reconnect(&self) -> DualSenseResult<DualSenseConnection> {
+        self.set_lifecycle(DualSenseLifecycle::Opening);
+        let result = async {
+            self.enable_bt_full_report().await?;
+            self.connect().await
+        }
+        .await;
+        self.set_lifecycle(match result {
+            Ok(_) => DualSenseLifecycle::Connected {
+                since: Instant::now(),
+            },
+            Err(_) => DualSenseLifecycle::Disconnected,
+        });
+        result
+    }
+
+    pub fn lifecycle(&self) -> DualSenseLifecycle {
+        *self.lifecycle.lock().unwrap()
+    }
+
+    /// For state changes observed outside of `DualSense`, e.g. a device manager noticing
+    /// the device was unplugged
+    pub fn set_lifecycle(&self, lifecycle: DualSenseLifecycle) {
+        *self.lifecycle.lock().unwrap() = lifecycle;
+    }
 
-        Ok(DualSenseConnection::new(reader, self.connection_type))
+    /// Reading the calibration report switches Bluetooth connections to full reports.
+    ///
+    /// This is a GET_REPORT on purpose: hid-playstation enables the mode the same way, as a
+    /// side effect of `dualsense_get_calibration_data`, and there is no report to send.
+    ///
+    /// The switch can silently not happen, so a report is read afterwards to check its size.
+    async fn enable_bt_full_report(&self) -> DualSenseResult<()> {
+        if self.connection_type != DualSenseConnectionType::BT {
+            return Ok(());
+        }
+        retry(
+            MAX_BT_FULL_REPORT_ATTEMPTS,
+            Duration::from_millis(RETRY_DELAY),
+            move || async move {
+                let mut buf = [0u8; DS_FEATURE_REPORT_BT_FULL_SIZE];
+                buf[0] = DS_FEATURE_REPORT_BT_FULL;
+                // a failed read shows up in the check below
+                let _ = self.device.read_feature_report(&mut buf).await;
+                check_bt_full_report(&self.device).await
+            },
+        )
+        .await
+    }
+
+    /// Opens a connection for reading input reports and writing output reports.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use async_hid::HidBackend;
+    /// # use dualsense_tray::dualsense::{async_hid::DualSense, error::DualSenseResult};
+    /// # use futures_lite::StreamExt;
+    /// # fn main() -> DualSenseResult<()> { smol::block_on(async {
+    /// # let hid = HidBackend::default();
+    /// # let device = DualSense::enumerate(&hid).await?.next().await.unwrap();
+    /// let ds = DualSense::open_device(device).await?;
+    /// let mut connection = ds.connect().await?;
+    /// let (capacity, charging) = connection.read_input_report().await?.battery();
+    /// println!("{capacity}%{}", if charging { ", charging" } else { "" });
+    /// # Ok(()) }) }
+    /// ```
+    pub async fn connect(&self) -> DualSenseResult<DualSenseConnection> {
+        let reader = open_reader(&self.device).await?;
+        let writer = timeout(OPEN_TIMEOUT, self.device.open_writeable()).await?;
+
+        Ok(DualSenseConnection::new(
+            reader,
+            writer,
+            self.connection_type,
+        ))
     }
 
     pub fn device_id(&self) -> &DeviceId {
         &self.device.id
     }
 
+    pub fn serial_number(&self) -> Option<&str> {
+        self.device
+            .serial_number
+            .as_deref()
+            .filter(|serial| !serial.is_empty())
+    }
+
+    /// System path of the HID device, when the platform identifies devices by path.
+    pub fn device_path(&self) -> Option<&str> {
+        match &self.device.id {
+            #[cfg(target_os = "linux")]
+            DeviceId::DevPath(path) => path.to_str(),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
     pub fn name(&self) -> String {
-        format!("DualSense {}", self.connection_type)
+        format!("{} {}", self.model(), self.connection_type)
+    }
+
+    /// Name to show instead of `name()`, e.g. "Left controller"
+    pub fn set_display_name(&mut self, name: String) {
+        self.display_name = Some(name);
+    }
+
+    /// The name set with `set_display_name`, or `name()`
+    pub fn display_name(&self) -> Cow<'_, str> {
+        match &self.display_name {
+            Some(name) => Cow::Borrowed(name),
+            None => Cow::Owned(self.name()),
+        }
+    }
+
+    pub fn model(&self) -> DualSenseModel {
+        DualSenseModel::from_product_id(self.device.product_id).unwrap_or(DualSenseModel::DualSense)
     }
 
     pub fn connection_type(&self) -> DualSenseConnectionType {
         self.connection_type
     }
+
+    /// Expected input report rate, e.g. to initialize IMU integrators
+    pub fn report_rate_hint(&self) -> u32 {
+        self.connection_type.report_rate_hint()
+    }
+
+    /// Firmware and hardware versions read when the device was opened
+    pub fn device_info(&self) -> Option<&DeviceInfoReport> {
+        self.device_info.as_ref()
+    }
+
+    /// Reads the gyroscope and accelerometer calibration, for `DualSenseInputReport::imu_data`
+    pub async fn read_imu_calibration(&self) -> DualSenseResult<ImuCalibration> {
+        let mut buf = [0u8; DS_FEATURE_REPORT_BT_FULL_SIZE];
+        buf[0] = DS_FEATURE_REPORT_BT_FULL;
+        let size = timeout(READ_TIMEOUT, self.device.read_feature_report(&mut buf)).await?;
+        ImuCalibration::parse(&buf[..size])
+            .cloned()
+            .ok_or(DualSenseError::InvalidFeatureReport(
+                DS_FEATURE_REPORT_BT_FULL,
+            ))
+    }
+
+    /// Reads the firmware info again to check the controller still answers, catching
+    /// handles that stay open while the device has stopped responding.
+    ///
+    /// `Ok(false)` when the answer isn't the firmware info read when the device was opened.
+    pub async fn health_check(&self) -> DualSenseResult<bool> {
+        let mut buf = [0u8; DS_FEATURE_REPORT_FIRMWARE_INFO_SIZE];
+        buf[0] = DS_FEATURE_REPORT_FIRMWARE_INFO;
+        let size = timeout(READ_TIMEOUT, self.device.read_feature_report(&mut buf)).await?;
+        Ok(match DeviceInfoReport::parse(&buf[..size]) {
+            Some(device_info) => self
+                .device_info
+                .as_ref()
+                .is_none_or(|opened| opened == device_info),
+            None => false,
+        })
+    }
+
+    /// Reads the Bluetooth addresses of the controller and of the console it is paired with
+    pub async fn read_pairing_info(&self) -> DualSenseResult<PairingInfo> {
+        let mut buf = [0u8; DS_FEATURE_REPORT_PAIRING_INFO_SIZE];
+        buf[0] = DS_FEATURE_REPORT_PAIRING_INFO;
+        let size = timeout(READ_TIMEOUT, self.device.read_feature_report(&mut buf)).await?;
+        PairingInfo::parse(&buf[..size])
+            .cloned()
+            .ok_or(DualSenseError::InvalidFeatureReport(
+                DS_FEATURE_REPORT_PAIRING_INFO,
+            ))
+    }
+}
+
+/// Reads the firmware info feature report 0x20
+pub async fn read_device_info_report(device: &Device) -> DualSenseResult<DeviceInfoReport> {
+    let mut buf = [0u8; DS_FEATURE_REPORT_FIRMWARE_INFO_SIZE];
+    buf[0] = DS_FEATURE_REPORT_FIRMWARE_INFO;
+    let size = timeout(READ_TIMEOUT, device.read_feature_report(&mut buf)).await?;
+    DeviceInfoReport::parse(&buf[..size])
+        .cloned()
+        .ok_or(DualSenseError::InvalidFeatureReport(
+            DS_FEATURE_REPORT_FIRMWARE_INFO,
+        ))
+}
+
+/// Detects the connection type without reading a report, which fails while
+/// a device that was just plugged in is still initializing.
+fn detect_connection_from_descriptor(device: &Device) -> Option<DualSenseConnectionType> {
+    #[cfg(target_os = "linux")]
+    if let DeviceId::DevPath(path) = &device.id {
+        let sysfs = std::path::Path::new("/sys/class/hidraw")
+            .join(path.file_name()?)
+            .join("device");
+        if let Some(connection_type) = detect_connection_from_uevent(&sysfs) {
+            return Some(connection_type);
+        }
+        let descriptor = std::fs::read(sysfs.join("report_descriptor")).ok()?;
+        return parse_hid_descriptor(&descriptor);
+    }
+
+    let _ = device;
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn detect_connection_from_uevent(sysfs: &std::path::Path) -> Option<DualSenseConnectionType> {
+    // HID_ID=<bus>:<vendor>:<product>, the bus tells USB and Bluetooth apart
+    let uevent = std::fs::read_to_string(sysfs.join("uevent")).ok()?;
+    let hid_id = uevent
+        .lines()
+        .find_map(|line| line.strip_prefix("HID_ID="))?;
+    let bus = u16::from_str_radix(hid_id.split(':').next()?, 16).ok()?;
+    match bus {
+        BUS_USB => Some(DualSenseConnectionType::USB),
+        BUS_BLUETOOTH => Some(DualSenseConnectionType::BT),
+        _ => None,
+    }
+}
+
+/// Runs `attempt` up to `max_attempts` times, waiting `delay` after each failure
+async fn retry<T, F, Fut>(max_attempts: u32, delay: Duration, mut attempt: F) -> DualSenseResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = DualSenseResult<T>>,
+{
+    let max_attempts = max_attempts.max(1);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(err) if attempts < max_attempts => {
+                tracing::debug!("Attempt {attempts}/{max_attempts} failed: {err}, retrying");
+                Timer::after(delay).await;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Reads one report and detects the connection type from its size
+async fn detect_connection_from_report(
+    device: &Device,
+) -> DualSenseResult<DualSenseConnectionType> {
+    let mut reader = open_reader(device).await?;
+
+    let mut buf = [0u8; DS_INPUT_REPORT_BT_SIZE];
+    let size = timeout(READ_TIMEOUT, reader.read_input_report(&mut buf)).await?;
+
+    DualSenseConnectionType::from_report_size(size).ok_or_else(|| {
+        tracing::warn!("Unknown report format: {:02x?} (size={})", &buf[..8], size);
+        DualSenseError::UnknownReportSize(size)
+    })
+}
+
+/// Reads one report and checks it is a Bluetooth full report
+async fn check_bt_full_report(device: &Device) -> DualSenseResult<()> {
+    let mut reader = open_reader(device).await?;
+
+    let mut buf = [0u8; DS_INPUT_REPORT_BT_SIZE];
+    let size = timeout(READ_TIMEOUT, reader.read_input_report(&mut buf)).await?;
+
+    match size {
+        DS_INPUT_REPORT_BT_SIZE => Ok(()),
+        DS_INPUT_REPORT_BT_NONFULL_SIZE => Err(DualSenseError::ReducedBluetoothReports),
+        _ => Err(DualSenseError::UnknownReportSize(size)),
+    }
+}
+
+async fn open_reader(device: &Device) -> DualSenseResult<DeviceReader> {
+    match timeout(OPEN_TIMEOUT, device.open_readable()).await {
+        Err(DualSenseError::HidError(err)) if is_permission_error(&err) => {
+            if let Some(fix) = suggest_permission_fix() {
+                tracing::warn!("Not allowed to open {}: {}", device.name, fix);
+            }
+            Err(DualSenseError::PermissionDenied)
+        }
+        result => result,
+    }
+}
+
+/// Runs `future`, failing with `DualSenseError::Timeout` after `timeout_ms`
+async fn timeout<T>(
+    timeout_ms: u64,
+    future: impl Future<Output = Result<T, HidError>>,
+) -> DualSenseResult<T> {
+    async { Ok(future.await?) }
+        .or(async {
+            Timer::after(Duration::from_millis(timeout_ms)).await;
+            Err(DualSenseError::Timeout)
+        })
+        .await
+}
+
+// async-hid has no dedicated variant, the OS error is only available through its message
+fn is_permission_error(err: &HidError) -> bool {
+    let message = err.to_string().to_lowercase();
+    message.contains("permission denied") || message.contains("access is denied")
+}
+
+/// Platform specific advice for when HID devices can't be opened due to missing permissions
+pub fn suggest_permission_fix() -> Option<&'static str> {
+    if cfg!(target_os = "linux") {
+        Some(
+            "add your user to the input group (sudo usermod -aG input $USER) \
+             or install a udev rule granting access to the hidraw device, then log in again",
+        )
+    } else {
+        None
+    }
 }
 
 pub struct DualSenseConnection {
     reader: DeviceReader,
+    writer: Mutex<DeviceWriter>,
+    output_seq: AtomicU8,
     connection_type: DualSenseConnectionType,
+    stats: DualSenseConnectionStats,
+    /// Read by `peek_input_report`, returned by the next `read_input_report`
+    pending_report: Option<DualSenseInputReport>,
 }
 
 impl DualSenseConnection {
-    fn new(reader: DeviceReader, connection_type: DualSenseConnectionType) -> Self {
+    fn new(
+        reader: DeviceReader,
+        writer: DeviceWriter,
+        connection_type: DualSenseConnectionType,
+    ) -> Self {
         Self {
             reader,
+            writer: Mutex::new(writer),
+            output_seq: AtomicU8::new(0),
             connection_type,
+            stats: DualSenseConnectionStats::new(),
+            pending_report: None,
         }
     }
 
-    pub async fn read_input_report(&mut self) -> HidResult<DualSenseInputReport> {
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use async_hid::HidBackend;
+    /// # use dualsense_tray::dualsense::{
+    /// #     async_hid::DualSense, error::DualSenseResult, proto::DualSenseOutputReportBuilder,
+    /// # };
+    /// # use futures_lite::StreamExt;
+    /// # fn main() -> DualSenseResult<()> { smol::block_on(async {
+    /// # let hid = HidBackend::default();
+    /// # let device = DualSense::enumerate(&hid).await?.next().await.unwrap();
+    /// # let connection = DualSense::open_device(device).await?.connect().await?;
+    /// let report = DualSenseOutputReportBuilder::new().lightbar(0, 0, 255).build();
+    /// connection.write_output_report(report).await?;
+    /// # Ok(()) }) }
+    /// ```
+    pub async fn write_output_report(&self, report: DualSenseOutputReport) -> DualSenseResult<()> {
+        let buf = match self.connection_type {
+            DualSenseConnectionType::USB => report.to_usb_bytes().to_vec(),
+            DualSenseConnectionType::BT => {
+                let seq = self.output_seq.fetch_add(1, Ordering::Relaxed);
+                report.to_bt_bytes(seq).to_vec()
+            }
+        };
+
+        let mut writer = self.writer.lock().await;
+        timeout(WRITE_TIMEOUT, writer.write_output_report(&buf)).await
+    }
+
+    pub fn stats(&self) -> &DualSenseConnectionStats {
+        &self.stats
+    }
+
+    /// Expected time between input reports
+    pub fn expected_interval_ms(&self) -> u64 {
+        1000 / self.connection_type.report_rate_hint() as u64
+    }
+
+    /// Waits for the next input report, failing with `DualSenseError::Timeout` when none
+    /// arrives in time.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use async_hid::HidBackend;
+    /// # use dualsense_tray::dualsense::{async_hid::DualSense, error::DualSenseResult, proto::Button};
+    /// # use futures_lite::StreamExt;
+    /// # fn main() -> DualSenseResult<()> { smol::block_on(async {
+    /// # let hid = HidBackend::default();
+    /// # let device = DualSense::enumerate(&hid).await?.next().await.unwrap();
+    /// # let mut connection = DualSense::open_device(device).await?.connect().await?;
+    /// loop {
+    ///     let report = connection.read_input_report().await?;
+    ///     if report.is_pressed(Button::Cross) {
+    ///         break;
+    ///     }
+    /// }
+    /// # Ok(()) }) }
+    /// ```
+    pub async fn read_input_report(&mut self) -> DualSenseResult<DualSenseInputReport> {
+        match self.pending_report.take() {
+            Some(report) => Ok(report),
+            None => self.read_next_report().await,
+        }
+    }
+
+    /// Waits for the next input report like `read_input_report`, but keeps it so the
+    /// following `read_input_report` returns it again. Peeking twice reads only once.
+    pub async fn peek_input_report(&mut self) -> DualSenseResult<&DualSenseInputReport> {
+        let report = match self.pending_report.take() {
+            Some(report) => report,
+            None => self.read_next_report().await?,
+        };
+        Ok(self.pending_report.insert(report))
+    }
+
+    /// Whether a report from `peek_input_report` is waiting to be read
+    pub fn has_pending_report(&self) -> bool {
+        self.pending_report.is_some()
+    }
+
+    async fn read_next_report(&mut self) -> DualSenseResult<DualSenseInputReport> {
         let mut buf = [0u8; DS_INPUT_REPORT_BT_SIZE];
-        let size = self
-            .reader
-            .read_input_report(&mut buf)
-            .or(async {
-                Timer::after(Duration::from_millis(READ_TIMEOUT)).await;
-                Err(HidError::Disconnected)
-            })
-            .await?;
+        let size = timeout(READ_TIMEOUT, self.reader.read_input_report(&mut buf)).await?;
 
         // device disconnected
         if size == 0 {
-            return Err(HidError::Disconnected);
+            return Err(HidError::Disconnected.into());
+        }
+        self.stats.record_report(Instant::now());
+
+        if self.connection_type == DualSenseConnectionType::BT {
+            let crc_offset = DS_INPUT_REPORT_BT_SIZE - 4;
+            let expected = ds_crc32(DS_INPUT_CRC32_SEED, &buf[..crc_offset]);
+            let actual = u32::from_le_bytes(buf[crc_offset..].try_into().unwrap());
+            if expected != actual {
+                return Err(DualSenseError::CrcMismatch { expected, actual });
+            }
         }
 
         let input_report: DualSenseInputReport = match self.connection_type {
@@ -173,6 +755,79 @@ impl DualSenseConnection {
         };
         Ok(input_report)
     }
+
+    /// Like `read_input_report`, but reopens the connection through `ds.reconnect()` when the
+    /// device disconnects or stops answering, so polling loops survive e.g. a wiggled USB
+    /// cable or a controller coming back in Bluetooth range.
+    pub async fn read_with_reconnect(
+        &mut self,
+        ds: &DualSense,
+    ) -> DualSenseResult<DualSenseInputReport> {
+        let mut attempts = 0;
+        loop {
+            match self.read_input_report().await {
+                Err(err) if err.is_disconnected() && attempts < MAX_RECONNECT_ATTEMPTS => {
+                    attempts += 1;
+                    ds.set_lifecycle(DualSenseLifecycle::Reconnecting { attempt: attempts });
+                    tracing::debug!(
+                        "{} disconnected, reconnecting ({attempts}/{MAX_RECONNECT_ATTEMPTS})",
+                        ds.name()
+                    );
+                    Timer::after(Duration::from_millis(RETRY_DELAY)).await;
+                    // the device may not be back yet, keep trying until the attempts run out.
+                    // `reconnect` updates the lifecycle either way.
+                    if let Ok(connection) = ds.reconnect().await {
+                        self.reader = connection.reader;
+                        self.writer = connection.writer;
+                    }
+                }
+                Err(err) if err.is_disconnected() => {
+                    ds.set_lifecycle(DualSenseLifecycle::Disconnected);
+                    return Err(err);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Moves the connection into a task that forwards its input reports to the returned
+    /// channel, which holds up to `buffer` reports, at least one. The channel closes once a
+    /// read fails, dropping the task stops reading.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use async_hid::HidBackend;
+    /// # use dualsense_tray::dualsense::{async_hid::DualSense, error::DualSenseResult};
+    /// # use futures_lite::StreamExt;
+    /// # fn main() -> DualSenseResult<()> { smol::block_on(async {
+    /// # let hid = HidBackend::default();
+    /// # let device = DualSense::enumerate(&hid).await?.next().await.unwrap();
+    /// # let connection = DualSense::open_device(device).await?.connect().await?;
+    /// let (reports, _task) = connection.into_channel(16);
+    /// while let Ok(report) = reports.recv().await {
+    ///     println!("{report}");
+    /// }
+    /// # Ok(()) }) }
+    /// ```
+    pub fn into_channel(mut self, buffer: usize) -> (Receiver<DualSenseInputReport>, Task<()>) {
+        let (sender, receiver) = channel::bounded(buffer.max(1));
+        let task = smol::spawn(async move {
+            loop {
+                let report = match self.read_input_report().await {
+                    Ok(report) => report,
+                    Err(err) => {
+                        tracing::debug!("Input report channel closed: {err}");
+                        return;
+                    }
+                };
+                if sender.send(report).await.is_err() {
+                    return;
+                }
+            }
+        });
+        (receiver, task)
+    }
 }
 
 #[cfg(test)]
@@ -199,6 +854,78 @@ mod tests {
         }
     }
 
+    #[test]
+    fn connection_report_ids() {
+        let usb = DualSenseConnectionType::USB;
+        let bt = DualSenseConnectionType::BT;
+        assert_eq!((usb.report_id(), usb.output_report_id()), (0x01, 0x02));
+        assert_eq!((bt.report_id(), bt.output_report_id()), (0x31, 0x31));
+        assert_eq!(
+            DualSenseOutputReport::default().to_usb_bytes()[0],
+            usb.output_report_id()
+        );
+        assert_eq!((usb.report_rate_hint(), bt.report_rate_hint()), (250, 250));
+    }
+
+    #[test]
+    fn connection_from_report_size() {
+        let usb = Some(DualSenseConnectionType::USB);
+        let bt = Some(DualSenseConnectionType::BT);
+        let sizes = [
+            DS_INPUT_REPORT_USB_SIZE,
+            DS_INPUT_REPORT_BT_SIZE,
+            DS_INPUT_REPORT_BT_NONFULL_SIZE,
+            32,
+        ];
+        assert_eq!(
+            sizes.map(DualSenseConnectionType::from_report_size),
+            [usb, bt, bt, None]
+        );
+    }
+
+    #[test]
+    fn lifecycle_display() {
+        assert_eq!(
+            DualSenseLifecycle::Reconnecting { attempt: 2 }.to_string(),
+            "Reconnecting (attempt 2/3)"
+        );
+        let connected = DualSenseLifecycle::Connected {
+            since: Instant::now(),
+        };
+        assert_eq!(connected.to_string(), "Connected");
+    }
+
+    #[apply(test!)]
+    async fn retry_succeeds_after_two_failures() {
+        let mut calls = 0;
+        let result = retry(3, Duration::ZERO, || {
+            calls += 1;
+            let calls = calls;
+            async move {
+                if calls < 3 {
+                    Err(DualSenseError::Timeout)
+                } else {
+                    Ok(DualSenseConnectionType::USB)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), DualSenseConnectionType::USB);
+        assert_eq!(calls, 3);
+    }
+
+    #[apply(test!)]
+    async fn retry_gives_up() {
+        let mut calls = 0;
+        let result = retry(3, Duration::ZERO, || {
+            calls += 1;
+            async { Err::<(), _>(DualSenseError::Timeout) }
+        })
+        .await;
+        assert!(matches!(result, Err(DualSenseError::Timeout)));
+        assert_eq!(calls, 3);
+    }
+
     #[apply(test!)]
     async fn concurrent_read_input_and_feature(ex: &LocalExecutor<'_>) {
         let hid = HidBackend::default();
@@ -213,7 +940,7 @@ mod tests {
                 let mut buf = [0u8; 128];
                 buf[0] = DS_FEATURE_REPORT_BT_FULL;
                 let size = ds.device.read_feature_report(&mut buf).await.unwrap();
-                assert!(size == 41);
+                assert_eq!(size, DS_FEATURE_REPORT_BT_FULL_SIZE);
                 buf[..size].to_vec()
             };
 
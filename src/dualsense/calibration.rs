@@ -0,0 +1,177 @@
+use super::proto::DualSenseInputReport;
+
+/// About one second of reports over Bluetooth
+pub const DEFAULT_CALIBRATION_SAMPLES: usize = 60;
+
+/// Deflection from the resting position treated as full until the stick is seen going
+/// further, so worn sticks that stop short of the edge still reach 1.0
+pub const MIN_STICK_EXTENT: u8 = 100;
+
+/// Computes the resting position of the sticks to compensate for drift, and learns how far
+/// they actually move.
+///
+/// The controller is assumed to be at rest while the first reports are collected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StickCalibrator {
+    samples: usize,
+    collected: usize,
+    sum: [u32; 4],
+    center_offset: (u8, u8),
+    right_center_offset: (u8, u8),
+    /// Lowest and highest raw value seen per axis, in `sticks_raw` order
+    range: [(u8, u8); 4],
+}
+
+impl StickCalibrator {
+    pub fn new() -> Self {
+        Self::with_samples(DEFAULT_CALIBRATION_SAMPLES)
+    }
+
+    pub fn with_samples(samples: usize) -> Self {
+        Self {
+            samples: samples.max(1),
+            collected: 0,
+            sum: [0; 4],
+            center_offset: (0x80, 0x80),
+            right_center_offset: (0x80, 0x80),
+            range: [(0x80, 0x80); 4],
+        }
+    }
+
+    /// Feeds a report into the collection window, returns `true` once calibrated. The range
+    /// keeps being learned from every report afterwards.
+    pub fn update(&mut self, report: &DualSenseInputReport) -> bool {
+        let (x, y, rx, ry) = report.sticks_raw();
+        let axes = [x, y, rx, ry];
+        for ((min, max), value) in self.range.iter_mut().zip(axes) {
+            (*min, *max) = ((*min).min(value), (*max).max(value));
+        }
+        if self.is_calibrated() {
+            return true;
+        }
+
+        for (sum, value) in self.sum.iter_mut().zip(axes) {
+            *sum += value as u32;
+        }
+        self.collected += 1;
+
+        if self.is_calibrated() {
+            let samples = self.samples as u32;
+            let [x, y, rx, ry] = self.sum.map(|sum| (sum / samples) as u8);
+            self.center_offset = (x, y);
+            self.right_center_offset = (rx, ry);
+        }
+        self.is_calibrated()
+    }
+
+    pub fn is_calibrated(&self) -> bool {
+        self.collected >= self.samples
+    }
+
+    /// Resting position of the left stick, the nominal center until calibrated.
+    pub fn center_offset(&self) -> (u8, u8) {
+        self.center_offset
+    }
+
+    /// Resting position of the right stick, the nominal center until calibrated.
+    pub fn right_center_offset(&self) -> (u8, u8) {
+        self.right_center_offset
+    }
+
+    /// Left stick as `(x, y)` in `-1.0..=1.0` around its resting position, with positive
+    /// `y` pointing up.
+    pub fn left_stick(&self, x: u8, y: u8) -> (f32, f32) {
+        let (cx, cy) = self.center_offset;
+        // HID reports Y growing downwards
+        (self.normalize(0, x, cx), -self.normalize(1, y, cy))
+    }
+
+    /// Right stick as `(x, y)` in `-1.0..=1.0` around its resting position, with positive
+    /// `y` pointing up.
+    pub fn right_stick(&self, rx: u8, ry: u8) -> (f32, f32) {
+        let (cx, cy) = self.right_center_offset;
+        (self.normalize(2, rx, cx), -self.normalize(3, ry, cy))
+    }
+
+    /// Restarts the collection window, keeping the previous offsets until it completes. The
+    /// learned range doesn't depend on them and is kept.
+    pub fn recalibrate(&mut self) {
+        self.collected = 0;
+        self.sum = [0; 4];
+    }
+
+    /// `value` relative to `center`, scaled by the furthest deflection seen on that side
+    fn normalize(&self, axis: usize, value: u8, center: u8) -> f32 {
+        let (min, max) = self.range[axis];
+        let extent = if value >= center {
+            max.saturating_sub(center)
+        } else {
+            center.saturating_sub(min)
+        };
+        let extent = extent.max(MIN_STICK_EXTENT) as f32;
+        ((value as f32 - center as f32) / extent).clamp(-1.0, 1.0)
+    }
+}
+
+impl Default for StickCalibrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dualsense::proto::DualSenseInputReportBuilder;
+
+    fn report(left: (u8, u8), right: (u8, u8)) -> DualSenseInputReport {
+        DualSenseInputReportBuilder::new()
+            .left_stick(left.0, left.1)
+            .right_stick(right.0, right.1)
+            .build()
+    }
+
+    #[test]
+    fn learns_center_of_both_sticks() {
+        let mut calibrator = StickCalibrator::with_samples(4);
+        let resting = report((0x90, 0x70), (0x78, 0x88));
+        for _ in 0..3 {
+            assert!(!calibrator.update(&resting));
+        }
+        assert_eq!(calibrator.center_offset(), (0x80, 0x80));
+        assert!(calibrator.update(&resting));
+        assert_eq!(calibrator.center_offset(), (0x90, 0x70));
+        assert_eq!(calibrator.right_center_offset(), (0x78, 0x88));
+        assert_eq!(calibrator.left_stick(0x90, 0x70), (0.0, 0.0));
+        assert_eq!(calibrator.right_stick(0x78, 0x88), (0.0, 0.0));
+
+        // the old offsets are kept until the new window completes
+        calibrator.recalibrate();
+        let centered = report((0x80, 0x80), (0x80, 0x80));
+        for _ in 0..3 {
+            calibrator.update(&centered);
+        }
+        assert_eq!(calibrator.right_center_offset(), (0x78, 0x88));
+        assert!(calibrator.update(&centered));
+        assert_eq!(calibrator.right_center_offset(), (0x80, 0x80));
+    }
+
+    #[test]
+    fn learns_range() {
+        let mut calibrator = StickCalibrator::with_samples(1);
+        calibrator.update(&report((0x80, 0x80), (0x80, 0x80)));
+        // full until the stick is seen going further
+        assert_eq!(
+            calibrator.left_stick(0x80 + MIN_STICK_EXTENT, 0x80),
+            (1.0, 0.0)
+        );
+
+        // a worn stick stopping 16 short of the edge on the right, pushed fully up
+        calibrator.update(&report((0xF0, 0x00), (0x80, 0x80)));
+        assert_eq!(calibrator.left_stick(0xF0, 0x00), (1.0, 1.0));
+        assert_eq!(calibrator.left_stick(0xB8, 0x40), (0.5, 0.5));
+        // each side of each axis is learned on its own
+        assert_eq!(calibrator.left_stick(0x80 - 50, 0xB2), (-0.5, -0.5));
+        assert_eq!(calibrator.right_stick(0xB2, 0x80), (0.5, 0.0));
+    }
+}
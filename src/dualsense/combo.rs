@@ -0,0 +1,107 @@
+use super::proto::{Button, DualSenseInputReport};
+
+/// Combos a `ComboDetector` holds at most
+pub const MAX_COMBOS: usize = 8;
+
+struct Combo {
+    buttons: Vec<Button>,
+    action: Box<dyn Fn() + Send>,
+    /// Whether every button was pressed in the previous report
+    active: bool,
+}
+
+/// Fires actions on button combinations, e.g. PS+Options to open the settings
+#[derive(Default)]
+pub struct ComboDetector {
+    combos: Vec<Combo>,
+}
+
+impl ComboDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `combo`, returning false without registering it once `MAX_COMBOS` are registered.
+    /// An empty combo never fires.
+    pub fn register(&mut self, combo: &[Button], action: impl Fn() + Send + 'static) -> bool {
+        if self.combos.len() >= MAX_COMBOS {
+            return false;
+        }
+        self.combos.push(Combo {
+            buttons: combo.to_vec(),
+            action: Box::new(action),
+            active: false,
+        });
+        true
+    }
+
+    /// Fires the action of every combo whose buttons are all pressed in `report` but
+    /// weren't all pressed in the previous one, so holding a combo fires it once.
+    pub fn update(&mut self, report: &DualSenseInputReport) {
+        for combo in &mut self.combos {
+            let active = !combo.buttons.is_empty()
+                && combo
+                    .buttons
+                    .iter()
+                    .all(|button| report.is_pressed(*button));
+            if active && !combo.active {
+                (combo.action)();
+            }
+            combo.active = active;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::dualsense::proto::DualSenseInputReportBuilder;
+
+    const SCREENSHOT: [Button; 4] = [Button::L1, Button::R1, Button::Create, Button::PS];
+
+    fn report(buttons: &[Button]) -> DualSenseInputReport {
+        buttons
+            .iter()
+            .fold(DualSenseInputReportBuilder::new(), |builder, button| {
+                builder.button(*button, true)
+            })
+            .build()
+    }
+
+    #[test]
+    fn combo_fires_on_rising_edge_only() {
+        let fired = Arc::new(AtomicUsize::new(0));
+        let mut detector = ComboDetector::new();
+        assert!(detector.register(&SCREENSHOT, {
+            let fired = fired.clone();
+            move || {
+                fired.fetch_add(1, Ordering::SeqCst);
+            }
+        }));
+
+        detector.update(&report(&SCREENSHOT[..3]));
+        assert_eq!(fired.load(Ordering::SeqCst), 0);
+
+        detector.update(&report(&SCREENSHOT));
+        detector.update(&report(&SCREENSHOT));
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+
+        detector.update(&report(&SCREENSHOT[1..]));
+        detector.update(&report(&SCREENSHOT));
+        assert_eq!(fired.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn register_limit() {
+        let mut detector = ComboDetector::new();
+        for _ in 0..MAX_COMBOS {
+            assert!(detector.register(&[Button::PS], || {}));
+        }
+        assert!(!detector.register(&[Button::PS], || {}));
+    }
+}
@@ -0,0 +1,23 @@
+use super::proto::DeadzoneShape;
+
+pub const DEFAULT_DEADZONE: f32 = 0.1;
+pub const DEFAULT_OPEN_MAX_RETRIES: u32 = 3;
+
+/// Per-device settings applied when decoding reports
+#[derive(Debug, Clone, PartialEq)]
+pub struct DualSenseConfig {
+    pub deadzone: f32,
+    pub deadzone_shape: DeadzoneShape,
+    /// Attempts at reading the first report when opening a device
+    pub open_max_retries: u32,
+}
+
+impl Default for DualSenseConfig {
+    fn default() -> Self {
+        Self {
+            deadzone: DEFAULT_DEADZONE,
+            deadzone_shape: DeadzoneShape::Circular,
+            open_max_retries: DEFAULT_OPEN_MAX_RETRIES,
+        }
+    }
+}
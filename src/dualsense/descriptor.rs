@@ -0,0 +1,138 @@
+use super::{
+    async_hid::DualSenseConnectionType,
+    proto::{
+        DS_INPUT_REPORT_BT, DS_INPUT_REPORT_BT_SIZE, DS_INPUT_REPORT_USB, DS_INPUT_REPORT_USB_SIZE,
+    },
+};
+
+// short item prefixes with the data size bits cleared
+const ITEM_INPUT: u8 = 0x80;
+const ITEM_REPORT_SIZE: u8 = 0x74;
+const ITEM_REPORT_ID: u8 = 0x84;
+const ITEM_REPORT_COUNT: u8 = 0x94;
+const ITEM_PUSH: u8 = 0xA4;
+const ITEM_POP: u8 = 0xB4;
+const ITEM_LONG: u8 = 0xFE;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Globals {
+    report_id: u8,
+    report_size: u32,
+    report_count: u32,
+}
+
+/// Detects the connection type from the HID report descriptor: the Bluetooth descriptor
+/// declares the full report 0x31, the USB one a 64 byte report 0x01.
+pub fn parse_hid_descriptor(descriptor: &[u8]) -> Option<DualSenseConnectionType> {
+    let sizes = input_report_sizes(descriptor)?;
+    let size_of = |id: u8| {
+        sizes
+            .iter()
+            .find(|(report_id, _)| *report_id == id)
+            .map(|(_, size)| *size)
+    };
+
+    // sizes don't include the report id
+    if size_of(DS_INPUT_REPORT_BT) == Some(DS_INPUT_REPORT_BT_SIZE - 1) {
+        Some(DualSenseConnectionType::BT)
+    } else if size_of(DS_INPUT_REPORT_USB) == Some(DS_INPUT_REPORT_USB_SIZE - 1) {
+        Some(DualSenseConnectionType::USB)
+    } else {
+        None
+    }
+}
+
+/// `(report id, size in bytes)` of every input report, `None` if the descriptor is truncated
+fn input_report_sizes(descriptor: &[u8]) -> Option<Vec<(u8, usize)>> {
+    let mut bits: Vec<(u8, u32)> = Vec::new();
+    let mut globals = Globals::default();
+    let mut stack = Vec::new();
+
+    let mut i = 0;
+    while i < descriptor.len() {
+        let prefix = descriptor[i];
+        if prefix == ITEM_LONG {
+            let size = *descriptor.get(i + 1)? as usize;
+            i += 3 + size;
+            continue;
+        }
+
+        let size = match prefix & 0x03 {
+            3 => 4,
+            size => size as usize,
+        };
+        let data = descriptor.get(i + 1..i + 1 + size)?;
+        let value = data
+            .iter()
+            .rev()
+            .fold(0u32, |acc, &byte| (acc << 8) | byte as u32);
+
+        match prefix & 0xFC {
+            ITEM_INPUT => {
+                let report_bits = globals.report_size.saturating_mul(globals.report_count);
+                match bits.iter_mut().find(|(id, _)| *id == globals.report_id) {
+                    Some((_, total)) => *total = total.saturating_add(report_bits),
+                    None => bits.push((globals.report_id, report_bits)),
+                }
+            }
+            ITEM_REPORT_SIZE => globals.report_size = value,
+            ITEM_REPORT_ID => globals.report_id = value as u8,
+            ITEM_REPORT_COUNT => globals.report_count = value,
+            ITEM_PUSH => stack.push(globals),
+            ITEM_POP => globals = stack.pop()?,
+            _ => {}
+        }
+        i += 1 + size;
+    }
+
+    Some(
+        bits.into_iter()
+            .map(|(id, bits)| (id, bits.div_ceil(8) as usize))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: [u8; 6] = [0x05, 0x01, 0x09, 0x05, 0xA1, 0x01];
+
+    #[test]
+    fn usb_descriptor() {
+        let descriptor = [
+            &HEADER[..],
+            // report 0x01: 63 bytes
+            &[0x85, 0x01, 0x75, 0x08, 0x95, 0x3F, 0x81, 0x02],
+            // output report 0x02, ignored
+            &[0x85, 0x02, 0x95, 0x2F, 0x91, 0x02, 0xC0],
+        ]
+        .concat();
+        assert_eq!(
+            parse_hid_descriptor(&descriptor),
+            Some(DualSenseConnectionType::USB)
+        );
+    }
+
+    #[test]
+    fn bt_descriptor() {
+        let descriptor = [
+            &HEADER[..],
+            // reduced report 0x01: 4 bits of hat and 4 of padding, then 8 bytes
+            &[0x85, 0x01, 0x75, 0x04, 0x95, 0x02, 0x81, 0x02],
+            &[0x75, 0x08, 0x95, 0x08, 0x81, 0x02],
+            // full report 0x31: 77 bytes
+            &[0x85, 0x31, 0x95, 0x4D, 0x81, 0x02, 0xC0],
+        ]
+        .concat();
+        assert_eq!(
+            parse_hid_descriptor(&descriptor),
+            Some(DualSenseConnectionType::BT)
+        );
+    }
+
+    #[test]
+    fn truncated_descriptor() {
+        assert_eq!(parse_hid_descriptor(&[0x85, 0x01, 0x95]), None);
+    }
+}
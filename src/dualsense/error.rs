@@ -0,0 +1,92 @@
+use async_hid::HidError;
+
+pub type DualSenseResult<T> = Result<T, DualSenseError>;
+
+/// Failures of the DualSense layer, with errors of the HID backend wrapped in `HidError`
+#[derive(Debug)]
+pub enum DualSenseError {
+    HidError(HidError),
+    /// An input report of a size that is neither USB nor Bluetooth
+    UnknownReportSize(usize),
+    /// A Bluetooth input report whose trailing CRC32 doesn't match its contents
+    CrcMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// A feature report, by id, that is too short or malformed
+    InvalidFeatureReport(u8),
+    /// The Bluetooth full report mode could not be enabled
+    ReducedBluetoothReports,
+    /// Not allowed to open the device, see `suggest_permission_fix`
+    PermissionDenied,
+    /// The device didn't answer in time, usually because it is gone
+    Timeout,
+    /// A HID device that isn't a DualSense, by product id
+    UnsupportedDevice(u16),
+}
+
+impl DualSenseError {
+    /// Whether the device is gone, or at least not answering anymore
+    pub fn is_disconnected(&self) -> bool {
+        matches!(
+            self,
+            Self::Timeout | Self::HidError(HidError::Disconnected | HidError::NotConnected)
+        )
+    }
+}
+
+impl From<HidError> for DualSenseError {
+    fn from(err: HidError) -> Self {
+        Self::HidError(err)
+    }
+}
+
+impl std::fmt::Display for DualSenseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::HidError(err) => write!(f, "{err}"),
+            Self::UnknownReportSize(size) => write!(f, "Unknown report size {size}"),
+            Self::CrcMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "CRC mismatch, expected {expected:08x} but got {actual:08x}"
+                )
+            }
+            Self::InvalidFeatureReport(id) => write!(f, "Invalid feature report 0x{id:02x}"),
+            Self::ReducedBluetoothReports => write!(
+                f,
+                "Bluetooth full report mode could not be enabled, the controller still sends reduced reports"
+            ),
+            Self::PermissionDenied => write!(f, "Permission denied"),
+            Self::Timeout => write!(f, "Timed out"),
+            Self::UnsupportedDevice(product_id) => {
+                write!(f, "Unsupported device with product id {product_id:04x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DualSenseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::HidError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeouts_count_as_disconnected() {
+        assert!(DualSenseError::Timeout.is_disconnected());
+        assert!(DualSenseError::from(HidError::Disconnected).is_disconnected());
+        assert!(!DualSenseError::UnknownReportSize(10).is_disconnected());
+        assert_eq!(
+            DualSenseError::UnknownReportSize(10).to_string(),
+            "Unknown report size 10"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use super::proto::{Button, DualSenseInputReport};
+
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_ABS: u16 = 0x03;
+
+pub const SYN_REPORT: u16 = 0x00;
+
+pub const ABS_X: u16 = 0x00;
+pub const ABS_Y: u16 = 0x01;
+pub const ABS_Z: u16 = 0x02;
+pub const ABS_RX: u16 = 0x03;
+pub const ABS_RY: u16 = 0x04;
+pub const ABS_RZ: u16 = 0x05;
+pub const ABS_HAT0X: u16 = 0x10;
+pub const ABS_HAT0Y: u16 = 0x11;
+
+pub const BTN_SOUTH: u16 = 0x130;
+pub const BTN_EAST: u16 = 0x131;
+pub const BTN_NORTH: u16 = 0x133;
+pub const BTN_WEST: u16 = 0x134;
+pub const BTN_TL: u16 = 0x136;
+pub const BTN_TR: u16 = 0x137;
+pub const BTN_TL2: u16 = 0x138;
+pub const BTN_TR2: u16 = 0x139;
+pub const BTN_SELECT: u16 = 0x13A;
+pub const BTN_START: u16 = 0x13B;
+pub const BTN_MODE: u16 = 0x13C;
+pub const BTN_THUMBL: u16 = 0x13D;
+pub const BTN_THUMBR: u16 = 0x13E;
+pub const BTN_LEFT: u16 = 0x110;
+pub const KEY_MICMUTE: u16 = 248;
+
+/// Same mapping as the hid-playstation kernel driver, with the touchpad click and the mute
+/// button folded into the gamepad device.
+const BUTTON_MAP: [(Button, u16); 15] = [
+    (Button::Square, BTN_WEST),
+    (Button::Cross, BTN_SOUTH),
+    (Button::Circle, BTN_EAST),
+    (Button::Triangle, BTN_NORTH),
+    (Button::L1, BTN_TL),
+    (Button::R1, BTN_TR),
+    (Button::L2, BTN_TL2),
+    (Button::R2, BTN_TR2),
+    (Button::Create, BTN_SELECT),
+    (Button::Options, BTN_START),
+    (Button::L3, BTN_THUMBL),
+    (Button::R3, BTN_THUMBR),
+    (Button::PS, BTN_MODE),
+    (Button::Touchpad, BTN_LEFT),
+    (Button::Mute, KEY_MICMUTE),
+];
+
+/// Mirrors `struct input_event` without the timestamp, which the kernel fills in on write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvdevEvent {
+    pub type_: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl EvdevEvent {
+    pub fn new(type_: u16, code: u16, value: i32) -> Self {
+        Self { type_, code, value }
+    }
+}
+
+/// Events for the fields that changed from `prev` to `report`, terminated by `SYN_REPORT`.
+/// Axes keep the raw 0..=255 range, like the kernel driver. An unchanged report yields only
+/// the `SYN_REPORT`.
+pub fn to_evdev_events(
+    report: &DualSenseInputReport,
+    prev: &DualSenseInputReport,
+) -> Vec<EvdevEvent> {
+    let mut events = Vec::new();
+
+    let (x, y, rx, ry) = report.sticks_raw();
+    let (z, rz) = report.triggers_raw();
+    let (hat_x, hat_y) = hat(report);
+    let (prev_x, prev_y, prev_rx, prev_ry) = prev.sticks_raw();
+    let (prev_z, prev_rz) = prev.triggers_raw();
+    let (prev_hat_x, prev_hat_y) = hat(prev);
+
+    for (code, value, prev_value) in [
+        (ABS_X, x as i32, prev_x as i32),
+        (ABS_Y, y as i32, prev_y as i32),
+        (ABS_RX, rx as i32, prev_rx as i32),
+        (ABS_RY, ry as i32, prev_ry as i32),
+        (ABS_Z, z as i32, prev_z as i32),
+        (ABS_RZ, rz as i32, prev_rz as i32),
+        (ABS_HAT0X, hat_x, prev_hat_x),
+        (ABS_HAT0Y, hat_y, prev_hat_y),
+    ] {
+        if value != prev_value {
+            events.push(EvdevEvent::new(EV_ABS, code, value));
+        }
+    }
+
+    for (button, code) in BUTTON_MAP {
+        let pressed = report.is_pressed(button);
+        if pressed != prev.is_pressed(button) {
+            events.push(EvdevEvent::new(EV_KEY, code, pressed as i32));
+        }
+    }
+
+    events.push(EvdevEvent::new(EV_SYN, SYN_REPORT, 0));
+    events
+}
+
+/// D-pad as `(x, y)` in `-1..=1`, with positive `y` pointing down
+fn hat(report: &DualSenseInputReport) -> (i32, i32) {
+    let dpad = report.dpad();
+    let x = dpad.right() as i32 - dpad.left() as i32;
+    let y = dpad.down() as i32 - dpad.up() as i32;
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dualsense::proto::{Button, DPad, DualSenseInputReportBuilder};
+
+    #[test]
+    fn only_changed_fields() {
+        let prev = DualSenseInputReportBuilder::new().build();
+        assert_eq!(
+            to_evdev_events(&prev, &prev),
+            [EvdevEvent::new(EV_SYN, SYN_REPORT, 0)]
+        );
+
+        // stick pushed right, Cross pressed and d-pad up
+        let next = DualSenseInputReportBuilder::new()
+            .left_stick(0xFF, 0x80)
+            .button(Button::Cross, true)
+            .dpad(DPad::Up)
+            .build();
+        assert_eq!(
+            to_evdev_events(&next, &prev),
+            [
+                EvdevEvent::new(EV_ABS, ABS_X, 0xFF),
+                EvdevEvent::new(EV_ABS, ABS_HAT0Y, -1),
+                EvdevEvent::new(EV_KEY, BTN_SOUTH, 1),
+                EvdevEvent::new(EV_SYN, SYN_REPORT, 0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use zerocopy::FromZeros;
+
+use super::proto::DualSenseInputReport;
+
+/// The last `N` input reports, without heap allocations.
+///
+/// Reports are kept in chronological order, the oldest is dropped by rotating the others
+/// forward. That costs a copy of the buffer per push, but keeps every window a contiguous
+/// slice, which gesture code can index directly.
+#[derive(Debug, Clone)]
+pub struct InputReportHistory<const N: usize> {
+    reports: [DualSenseInputReport; N],
+    len: usize,
+}
+
+impl<const N: usize> InputReportHistory<N> {
+    pub fn new() -> Self {
+        Self {
+            reports: core::array::from_fn(|_| DualSenseInputReport::new_zeroed()),
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, report: DualSenseInputReport) {
+        if N == 0 {
+            return;
+        }
+        if self.len < N {
+            self.reports[self.len] = report;
+            self.len += 1;
+        } else {
+            self.reports.rotate_left(1);
+            self.reports[N - 1] = report;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn latest(&self) -> Option<&DualSenseInputReport> {
+        self.window(1).first()
+    }
+
+    /// Oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = &DualSenseInputReport> {
+        self.reports[..self.len].iter()
+    }
+
+    /// The most recent `n` reports, oldest first. Shorter than `n` until enough were pushed.
+    pub fn window(&self, n: usize) -> &[DualSenseInputReport] {
+        let n = n.min(self.len);
+        &self.reports[self.len - n..self.len]
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl<const N: usize> Default for InputReportHistory<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dualsense::proto::DualSenseInputReportBuilder;
+
+    fn report(seq: u8) -> DualSenseInputReport {
+        DualSenseInputReportBuilder::new().seq(seq).build()
+    }
+
+    fn seqs<'a>(reports: impl IntoIterator<Item = &'a DualSenseInputReport>) -> Vec<u8> {
+        reports.into_iter().map(|r| r.seq_number()).collect()
+    }
+
+    #[test]
+    fn overwrites_oldest() {
+        let mut history = InputReportHistory::<3>::new();
+        assert!(history.latest().is_none());
+
+        history.push(report(1));
+        history.push(report(2));
+        assert_eq!(seqs(history.iter()), [1, 2]);
+        assert_eq!(seqs(history.window(5)), [1, 2]);
+
+        history.push(report(3));
+        history.push(report(4));
+        history.push(report(5));
+        assert_eq!(history.len(), 3);
+        assert_eq!(seqs(history.iter()), [3, 4, 5]);
+        assert_eq!(seqs(history.window(2)), [4, 5]);
+        assert_eq!(history.latest().map(|r| r.seq_number()), Some(5));
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use super::proto::{Button, DualSenseInputReport};
+
+pub const DEFAULT_SHORT_HOLD: Duration = Duration::from_millis(500);
+pub const DEFAULT_LONG_HOLD: Duration = Duration::from_millis(2000);
+
+/// A button that has been held past one of the thresholds of a `ButtonHoldTracker`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ButtonHoldEvent {
+    pub button: Button,
+    pub held_duration: Duration,
+}
+
+#[derive(Debug, Clone)]
+struct Hold {
+    since: Instant,
+    /// Thresholds already reported for this press
+    crossed: usize,
+}
+
+/// Detects long presses, e.g. holding PS for a few seconds to trigger an action
+#[derive(Debug, Clone)]
+pub struct ButtonHoldTracker {
+    thresholds: [Duration; 2],
+    holds: HashMap<Button, Hold>,
+    events: Vec<ButtonHoldEvent>,
+}
+
+impl ButtonHoldTracker {
+    pub fn new(short_hold: Duration, long_hold: Duration) -> Self {
+        Self {
+            thresholds: [short_hold, long_hold],
+            holds: HashMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Returns one event per threshold crossed since the previous report. Each threshold is
+    /// reported once per press, a button held through both yields two events.
+    pub fn update(&mut self, report: &DualSenseInputReport, now: Instant) -> &[ButtonHoldEvent] {
+        self.events.clear();
+        self.holds.retain(|button, _| report.is_pressed(*button));
+
+        for button in report.pressed_buttons() {
+            let hold = self.holds.entry(button).or_insert(Hold {
+                since: now,
+                crossed: 0,
+            });
+            let held_duration = now.saturating_duration_since(hold.since);
+            while hold.crossed < self.thresholds.len()
+                && held_duration >= self.thresholds[hold.crossed]
+            {
+                hold.crossed += 1;
+                self.events.push(ButtonHoldEvent {
+                    button,
+                    held_duration,
+                });
+            }
+        }
+        &self.events
+    }
+
+    /// How long `button` has been held as of `now`, `None` if it was released
+    pub fn held_duration(&self, button: Button, now: Instant) -> Option<Duration> {
+        let hold = self.holds.get(&button)?;
+        Some(now.saturating_duration_since(hold.since))
+    }
+
+    pub fn is_held_for(&self, button: Button, duration: Duration, now: Instant) -> bool {
+        self.held_duration(button, now)
+            .is_some_and(|held| held >= duration)
+    }
+}
+
+impl Default for ButtonHoldTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_SHORT_HOLD, DEFAULT_LONG_HOLD)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dualsense::proto::DualSenseInputReportBuilder;
+
+    fn report(ps_pressed: bool) -> DualSenseInputReport {
+        DualSenseInputReportBuilder::new()
+            .button(Button::PS, ps_pressed)
+            .build()
+    }
+
+    #[test]
+    fn hold_thresholds_fire_once() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut tracker = ButtonHoldTracker::default();
+
+        assert!(tracker.update(&report(true), at(0)).is_empty());
+        assert!(tracker.update(&report(true), at(300)).is_empty());
+        assert_eq!(
+            tracker.update(&report(true), at(600)),
+            &[ButtonHoldEvent {
+                button: Button::PS,
+                held_duration: Duration::from_millis(600),
+            }]
+        );
+        assert!(tracker.update(&report(true), at(1900)).is_empty());
+        assert_eq!(tracker.update(&report(true), at(2100)).len(), 1);
+        assert!(tracker.update(&report(true), at(3000)).is_empty());
+        assert!(tracker.is_held_for(Button::PS, Duration::from_secs(3), at(3000)));
+
+        // releasing starts over, a late report crosses both thresholds at once
+        assert!(tracker.update(&report(false), at(3100)).is_empty());
+        assert_eq!(tracker.held_duration(Button::PS, at(3100)), None);
+        assert!(tracker.update(&report(true), at(3200)).is_empty());
+        assert_eq!(tracker.update(&report(true), at(5500)).len(), 2);
+    }
+}
@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant};
+
+use super::proto::DualSenseInputReport;
+
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Stick magnitude considered as intentional movement
+const IDLE_STICK_THRESHOLD: f32 = 0.2;
+
+/// Detects controllers that have not been touched for a while.
+///
+/// Only the reports it is given are seen. Fed one report per status poll, as the device
+/// manager does, a changed input since the previous report also counts as activity so that
+/// sticks and buttons used in between aren't missed as long as they were left elsewhere. A
+/// controller is still reported idle up to one poll interval late, so the timeout should be
+/// several poll intervals long.
+#[derive(Debug, Clone)]
+pub struct IdleDetector {
+    timeout: Duration,
+    last_activity: Option<Instant>,
+    previous: Option<DualSenseInputReport>,
+    idle: bool,
+}
+
+impl IdleDetector {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            last_activity: None,
+            previous: None,
+            idle: false,
+        }
+    }
+
+    /// Returns `true` on the first report after the idle timeout is crossed. The first report
+    /// starts the timeout if it shows no activity.
+    pub fn update(&mut self, report: &DualSenseInputReport, now: Instant) -> bool {
+        let previous = self.previous.replace(report.clone());
+        if is_active(report) || previous.is_some_and(|previous| has_changed(report, &previous)) {
+            self.last_activity = Some(now);
+            self.idle = false;
+            return false;
+        }
+
+        let last_activity = *self.last_activity.get_or_insert(now);
+        if !self.idle && now.saturating_duration_since(last_activity) >= self.timeout {
+            self.idle = true;
+            return true;
+        }
+        false
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+}
+
+impl Default for IdleDetector {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDLE_TIMEOUT)
+    }
+}
+
+fn is_active(report: &DualSenseInputReport) -> bool {
+    let moved = |(x, y): (f32, f32)| x.hypot(y) > IDLE_STICK_THRESHOLD;
+    report.has_button_input() || moved(report.left_stick()) || moved(report.right_stick())
+}
+
+fn has_changed(report: &DualSenseInputReport, previous: &DualSenseInputReport) -> bool {
+    let moved =
+        |(x, y): (f32, f32), (px, py): (f32, f32)| (x - px).hypot(y - py) > IDLE_STICK_THRESHOLD;
+    let (pressed, released) = report.buttons_changed(previous);
+    !pressed.is_empty()
+        || !released.is_empty()
+        || report.dpad_changed(previous).is_some()
+        || moved(report.left_stick(), previous.left_stick())
+        || moved(report.right_stick(), previous.right_stick())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dualsense::proto::{Button, DualSenseInputReportBuilder};
+
+    const TIMEOUT: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn idle_once_after_timeout() {
+        let start = Instant::now();
+        let mut detector = IdleDetector::new(TIMEOUT);
+        // slight drift below the threshold is no activity
+        let resting = DualSenseInputReportBuilder::new()
+            .left_stick(0x88, 0x80)
+            .build();
+
+        assert!(!detector.update(&resting, start));
+        assert!(!detector.update(&resting, start + TIMEOUT / 2));
+        assert!(!detector.is_idle());
+        assert!(detector.update(&resting, start + TIMEOUT));
+        assert!(detector.is_idle());
+        assert!(!detector.update(&resting, start + TIMEOUT * 2));
+        assert!(detector.is_idle());
+    }
+
+    #[test]
+    fn activity_restarts_timeout() {
+        let start = Instant::now();
+        let mut detector = IdleDetector::new(TIMEOUT);
+        let resting = DualSenseInputReportBuilder::new().build();
+        let pressed = DualSenseInputReportBuilder::new()
+            .button(Button::Cross, true)
+            .build();
+        let moved = DualSenseInputReportBuilder::new()
+            .right_stick(0xFF, 0x80)
+            .build();
+
+        detector.update(&resting, start);
+        assert!(detector.update(&resting, start + TIMEOUT));
+        assert!(!detector.update(&pressed, start + TIMEOUT * 2));
+        assert!(!detector.is_idle());
+
+        let later = start + TIMEOUT * 2;
+        assert!(!detector.update(&moved, later + TIMEOUT / 2));
+        // letting go of the stick is a change too
+        assert!(!detector.update(&resting, later + TIMEOUT));
+        assert!(!detector.update(&resting, later + TIMEOUT * 3 / 2));
+        assert!(detector.update(&resting, later + TIMEOUT * 2));
+    }
+
+    #[test]
+    fn input_changed_between_reports_is_activity() {
+        let start = Instant::now();
+        let mut detector = IdleDetector::new(TIMEOUT);
+        let resting = DualSenseInputReportBuilder::new().build();
+        // both below the threshold, the stick moved from one to the other between reports
+        let up = DualSenseInputReportBuilder::new()
+            .left_stick(0x80, 0x6C)
+            .build();
+        let down = DualSenseInputReportBuilder::new()
+            .left_stick(0x80, 0x94)
+            .build();
+
+        detector.update(&resting, start);
+        assert!(!detector.update(&up, start + TIMEOUT / 2));
+        assert!(!detector.update(&down, start + TIMEOUT));
+        assert!(!detector.update(&down, start + TIMEOUT * 3 / 2));
+        assert!(detector.update(&down, start + TIMEOUT * 2));
+    }
+}
@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+
+use super::proto::DualSenseInputReport;
+
+/// Human readable view of an input report, as served to web dashboards
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecodedInputReport {
+    pub sticks: Sticks,
+    pub triggers: Triggers,
+    /// Names of the pressed buttons, as in `Button`'s `Display`
+    pub buttons: Vec<String>,
+    pub dpad: String,
+    /// Active touch points, sorted by tracking id
+    pub touch: Vec<TouchPoint>,
+    pub imu: Imu,
+    pub battery: Battery,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sticks {
+    pub left: (f32, f32),
+    pub right: (f32, f32),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Triggers {
+    pub left: f32,
+    pub right: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TouchPoint {
+    pub id: u8,
+    pub x: u16,
+    pub y: u16,
+}
+
+/// Raw sensor readings, signed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Imu {
+    pub gyro: [i16; 3],
+    pub accel: [i16; 3],
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Battery {
+    pub capacity: u8,
+    pub charging: bool,
+}
+
+impl From<&DualSenseInputReport> for DecodedInputReport {
+    fn from(report: &DualSenseInputReport) -> Self {
+        let (capacity, charging) = report.battery();
+        Self {
+            sticks: Sticks {
+                left: report.left_stick(),
+                right: report.right_stick(),
+            },
+            triggers: Triggers {
+                left: report.left_trigger(),
+                right: report.right_trigger(),
+            },
+            buttons: report
+                .pressed_buttons()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            dpad: report.dpad().to_string(),
+            touch: report
+                .sorted_touch_points()
+                .into_iter()
+                .filter(|point| point.is_active() && point.is_valid())
+                .map(|point| TouchPoint {
+                    id: point.id(),
+                    x: point.x(),
+                    y: point.y(),
+                })
+                .collect(),
+            imu: Imu {
+                gyro: report.raw_gyro().map(|v| v as i16),
+                accel: report.raw_accel().map(|v| v as i16),
+            },
+            battery: Battery { capacity, charging },
+        }
+    }
+}
+
+impl From<&DualSenseInputReport> for serde_json::Value {
+    fn from(report: &DualSenseInputReport) -> Self {
+        to_json_value(report)
+    }
+}
+
+/// JSON object with the keys `sticks`, `triggers`, `buttons`, `dpad`, `touch`, `imu` and `battery`
+pub fn to_json_value(report: &DualSenseInputReport) -> serde_json::Value {
+    serde_json::to_value(DecodedInputReport::from(report))
+        .expect("DecodedInputReport only contains JSON compatible types")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dualsense::proto::{Button, DPad, DualSenseInputReportBuilder, DualSenseTouchPoint};
+
+    #[test]
+    fn json_round_trip() {
+        // only the second touch point is active
+        let report = DualSenseInputReportBuilder::new()
+            .left_stick(0xFF, 0x80)
+            .right_stick(0x80, 0x00)
+            .left_trigger(0x40)
+            .button(Button::Cross, true)
+            .dpad(DPad::Right)
+            .touch_point(1, DualSenseTouchPoint::new(3, 0x100, 0x10, true))
+            .battery(50, true)
+            .build();
+
+        let value = to_json_value(&report);
+        for key in [
+            "sticks", "triggers", "buttons", "dpad", "touch", "imu", "battery",
+        ] {
+            assert!(value.get(key).is_some(), "missing {key}");
+        }
+        assert_eq!(value["buttons"], serde_json::json!(["Cross"]));
+        assert_eq!(value["dpad"], "Right");
+
+        let decoded: DecodedInputReport = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded, DecodedInputReport::from(&report));
+        assert_eq!(
+            decoded.touch,
+            [TouchPoint {
+                id: 3,
+                x: 0x100,
+                y: 0x10
+            }]
+        );
+        assert_eq!(
+            decoded.battery,
+            Battery {
+                capacity: 50,
+                charging: true
+            }
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::time::{Duration, Instant};
+
+use super::{
+    async_hid::DualSenseConnection,
+    error::{DualSenseError, DualSenseResult},
+    proto::{DualSenseInputReport, DualSenseOutputReportBuilder},
+};
+
+pub const DEFAULT_LATENCY_SAMPLES: usize = 100;
+/// Accelerometer change from the resting reading that counts as the rumble being felt
+const VIBRATION_THRESHOLD_G: f32 = 0.05;
+/// Time for the motors to stop between two pulses
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+/// A pulse is given up on if the controller doesn't shake within this time
+const DETECTION_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Round trip times of a latency test
+#[derive(Debug, Clone, Default)]
+pub struct LatencySamples {
+    samples: Vec<Duration>,
+}
+
+impl LatencySamples {
+    pub fn push(&mut self, sample: Duration) {
+        self.samples.push(sample);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn mean_ms(&self) -> f32 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        let total: Duration = self.samples.iter().sum();
+        as_ms(total) / self.samples.len() as f32
+    }
+
+    pub fn p95_ms(&self) -> f32 {
+        self.percentile_ms(95)
+    }
+
+    pub fn p99_ms(&self) -> f32 {
+        self.percentile_ms(99)
+    }
+
+    /// Nearest-rank percentile, 0 without samples
+    fn percentile_ms(&self, percent: usize) -> f32 {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        let rank = (sorted.len() * percent).div_ceil(100);
+        match rank.checked_sub(1).and_then(|index| sorted.get(index)) {
+            Some(sample) => as_ms(*sample),
+            None => 0.0,
+        }
+    }
+}
+
+fn as_ms(duration: Duration) -> f32 {
+    duration.as_micros() as f32 / 1000.0
+}
+
+/// Writes a rumble pulse and times until the accelerometer shows the controller vibrating,
+/// `samples` times. The rumble is stopped and left to settle before every pulse.
+///
+/// The controller should lie still, as moving it looks like vibration. Pulses that aren't
+/// felt within `DETECTION_TIMEOUT` are left out, so there may be fewer than `samples`.
+pub async fn measure_round_trip(
+    conn: &mut DualSenseConnection,
+    samples: usize,
+) -> DualSenseResult<LatencySamples> {
+    let pulse = DualSenseOutputReportBuilder::new().rumble(0, 128).build();
+    let stop = DualSenseOutputReportBuilder::new().rumble(0, 0).build();
+
+    let mut latencies = LatencySamples::default();
+    let result = async {
+        for _ in 0..samples {
+            conn.write_output_report(stop.clone()).await?;
+            let settle_start = Instant::now();
+            let mut resting = conn.read_input_report().await?;
+            while settle_start.elapsed() < SETTLE_TIME {
+                resting = conn.read_input_report().await?;
+            }
+
+            let sent = Instant::now();
+            conn.write_output_report(pulse.clone()).await?;
+            loop {
+                let report = conn.read_input_report().await?;
+                if is_vibrating(&resting, &report) {
+                    latencies.push(sent.elapsed());
+                    break;
+                }
+                if sent.elapsed() >= DETECTION_TIMEOUT {
+                    tracing::debug!("No vibration detected within {DETECTION_TIMEOUT:?}");
+                    break;
+                }
+            }
+        }
+        Ok::<_, DualSenseError>(())
+    }
+    .await;
+    // the motors keep running after a failed read otherwise
+    conn.write_output_report(stop).await?;
+    result.map(|()| latencies)
+}
+
+fn is_vibrating(resting: &DualSenseInputReport, report: &DualSenseInputReport) -> bool {
+    let resting = resting.accel_g();
+    let accel = report.accel_g();
+    (0..3).any(|i| (accel[i] - resting[i]).abs() > VIBRATION_THRESHOLD_G)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dualsense::proto::DualSenseInputReportBuilder;
+
+    #[test]
+    fn latency_percentiles() {
+        let mut samples = LatencySamples::default();
+        assert_eq!(samples.p95_ms(), 0.0);
+
+        for ms in 1..=100 {
+            samples.push(Duration::from_millis(ms));
+        }
+        assert_eq!(samples.mean_ms(), 50.5);
+        assert_eq!(samples.p95_ms(), 95.0);
+        assert_eq!(samples.p99_ms(), 99.0);
+    }
+
+    #[test]
+    fn vibration_from_accel_change() {
+        let resting = DualSenseInputReportBuilder::new().accel(0, 8192, 0).build();
+        let noise = DualSenseInputReportBuilder::new()
+            .accel(100, 8150, 0)
+            .build();
+        let shaking = DualSenseInputReportBuilder::new()
+            .accel(0, 8192, 900)
+            .build();
+        assert!(!is_vibrating(&resting, &noise));
+        assert!(is_vibrating(&resting, &shaking));
+    }
+}
@@ -0,0 +1,140 @@
+use std::time::{Duration, Instant};
+
+use async_io::Timer;
+use futures_lite::{Stream, stream};
+
+use super::proto::DualSenseInputReport;
+
+/// A report and when it was received, relative to the start of the recording
+pub type MacroStep = (Duration, DualSenseInputReport);
+
+/// Records input reports with their timing, e.g. a combo to replay later with `MacroPlayer`
+#[derive(Debug, Clone)]
+pub struct MacroRecorder {
+    started: Instant,
+    steps: Vec<MacroStep>,
+}
+
+impl MacroRecorder {
+    pub fn start() -> Self {
+        Self::start_at(Instant::now())
+    }
+
+    pub fn start_at(now: Instant) -> Self {
+        Self {
+            started: now,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, report: &DualSenseInputReport) {
+        self.record_at(report, Instant::now());
+    }
+
+    pub fn record_at(&mut self, report: &DualSenseInputReport, now: Instant) {
+        let offset = now.saturating_duration_since(self.started);
+        self.steps.push((offset, report.clone()));
+    }
+
+    pub fn steps(&self) -> &[MacroStep] {
+        &self.steps
+    }
+
+    pub fn finish(self) -> Vec<MacroStep> {
+        self.steps
+    }
+}
+
+/// Replays recorded reports with the delays they were recorded with
+#[derive(Debug, Clone)]
+pub struct MacroPlayer {
+    steps: Vec<MacroStep>,
+}
+
+impl MacroPlayer {
+    pub fn new(steps: Vec<MacroStep>) -> Self {
+        Self { steps }
+    }
+
+    /// Plays the macro once, timed from when the stream is first polled
+    pub fn play_once(&self) -> impl Stream<Item = DualSenseInputReport> {
+        self.play(false)
+    }
+
+    /// Plays the macro over and over, every pass starting right after the last report of
+    /// the previous one. Ends right away on an empty macro.
+    pub fn play_loop(&self) -> impl Stream<Item = DualSenseInputReport> {
+        self.play(true)
+    }
+
+    fn play(&self, repeat: bool) -> impl Stream<Item = DualSenseInputReport> {
+        stream::unfold((0, None::<Instant>), move |(index, started)| async move {
+            let (index, started) = match self.steps.get(index) {
+                Some(_) => (index, started),
+                None if repeat && !self.steps.is_empty() => (0, None),
+                None => return None,
+            };
+            let (offset, report) = &self.steps[index];
+            let started = started.unwrap_or_else(Instant::now);
+            Timer::at(started + *offset).await;
+            Some((report.clone(), (index + 1, Some(started))))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::StreamExt;
+    use macro_rules_attribute::apply;
+    use smol_macros::test;
+
+    use super::*;
+    use crate::dualsense::proto::{Button, DualSenseInputReportBuilder};
+
+    fn recorded() -> Vec<MacroStep> {
+        let start = Instant::now();
+        let mut recorder = MacroRecorder::start_at(start);
+        for (i, button) in [Button::Cross, Button::Circle, Button::Square]
+            .into_iter()
+            .enumerate()
+        {
+            let report = DualSenseInputReportBuilder::new()
+                .button(button, true)
+                .build();
+            recorder.record_at(&report, start + Duration::from_millis(10 * i as u64));
+        }
+        recorder.finish()
+    }
+
+    #[apply(test!)]
+    async fn play_once_keeps_order_and_timing() {
+        let steps = recorded();
+        assert_eq!(
+            steps.iter().map(|(offset, _)| *offset).collect::<Vec<_>>(),
+            [0, 10, 20].map(Duration::from_millis)
+        );
+
+        let player = MacroPlayer::new(steps.clone());
+        let started = Instant::now();
+        let played = player.play_once().collect::<Vec<_>>().await;
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert_eq!(
+            played,
+            steps
+                .into_iter()
+                .map(|(_, report)| report)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[apply(test!)]
+    async fn play_loop_starts_over() {
+        let player = MacroPlayer::new(recorded());
+        let played = player.play_loop().take(4).collect::<Vec<_>>().await;
+        assert!(played[0].is_pressed(Button::Cross));
+        assert!(played[3].is_pressed(Button::Cross));
+
+        let empty = MacroPlayer::new(Vec::new());
+        assert_eq!(std::pin::pin!(empty.play_loop()).next().await, None);
+    }
+}
@@ -1,2 +1,26 @@
 pub mod async_hid;
+pub mod calibration;
+pub mod combo;
+pub mod config;
+pub mod descriptor;
+pub mod error;
+#[cfg(feature = "linux")]
+pub mod evdev;
+pub mod history;
+pub mod hold;
+pub mod idle;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod latency;
+pub mod macros;
+pub mod multi;
 pub mod proto;
+pub mod sink;
+pub mod smoothing;
+pub mod state;
+pub mod stats;
+#[cfg(all(feature = "touchpad_mouse", target_os = "windows"))]
+pub mod touchpad_mouse;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod xinput;
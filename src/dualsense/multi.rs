@@ -0,0 +1,102 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Stream;
+
+use super::{error::DualSenseResult, proto::DualSenseInputReport};
+
+/// Merges the report streams of several devices, tagging each report with its stream index.
+///
+/// Streams are polled round-robin starting after the last one that produced a report,
+/// so a fast device cannot starve the others.
+pub fn merge_device_streams<S>(streams: Vec<S>) -> MergedDeviceStreams<S>
+where
+    S: Stream<Item = DualSenseResult<DualSenseInputReport>> + Send,
+{
+    MergedDeviceStreams {
+        streams: streams.into_iter().map(|s| Some(Box::pin(s))).collect(),
+        next: 0,
+    }
+}
+
+pub struct MergedDeviceStreams<S> {
+    streams: Vec<Option<Pin<Box<S>>>>,
+    next: usize,
+}
+
+impl<S> Stream for MergedDeviceStreams<S>
+where
+    S: Stream<Item = DualSenseResult<DualSenseInputReport>>,
+{
+    type Item = (usize, DualSenseResult<DualSenseInputReport>);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let len = this.streams.len();
+
+        for offset in 0..len {
+            let index = (this.next + offset) % len;
+            let Some(stream) = &mut this.streams[index] else {
+                continue;
+            };
+            match stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    this.next = (index + 1) % len;
+                    return Poll::Ready(Some((index, item)));
+                }
+                Poll::Ready(None) => this.streams[index] = None,
+                Poll::Pending => {}
+            }
+        }
+
+        if this.streams.iter().all(Option::is_none) {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::{StreamExt, stream};
+
+    use super::*;
+    use crate::dualsense::{error::DualSenseError, proto::DualSenseInputReportBuilder};
+
+    fn report() -> DualSenseInputReport {
+        DualSenseInputReportBuilder::new().build()
+    }
+
+    #[test]
+    fn all_streams_contribute() {
+        let streams = (0..3)
+            .map(|i| stream::iter((0..=i).map(|_| Ok::<_, DualSenseError>(report()))).boxed())
+            .collect();
+        let merged = smol::block_on(merge_device_streams(streams).collect::<Vec<_>>());
+
+        let mut counts = [0; 3];
+        for (index, report) in merged {
+            assert!(report.is_ok());
+            counts[index] += 1;
+        }
+        assert_eq!(counts, [1, 2, 3]);
+    }
+
+    #[test]
+    fn fast_stream_does_not_starve_others() {
+        let streams = vec![
+            stream::repeat_with(|| Ok::<_, DualSenseError>(report())).boxed(),
+            stream::repeat_with(|| Ok::<_, DualSenseError>(report())).boxed(),
+        ];
+        let indices = smol::block_on(
+            merge_device_streams(streams)
+                .take(6)
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(indices, [0, 1, 0, 1, 0, 1]);
+    }
+}
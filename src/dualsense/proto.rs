@@ -1,22 +1,375 @@
-use static_assertions::const_assert_eq;
-use zerocopy::byteorder::{LittleEndian as LE, U16, U32};
-use zerocopy::{FromBytes, Immutable, KnownLayout};
+use bitflags::bitflags;
+use zerocopy::byteorder::{I16, LittleEndian as LE, U16, U32};
+use zerocopy::{FromBytes, FromZeros, Immutable, IntoBytes, KnownLayout};
+
+use super::{
+    calibration::StickCalibrator,
+    smoothing::{SmoothingFilter, StickKalmanFilter},
+};
 
 pub const SONY_VID: u16 = 0x054C;
 pub const DUALSENSE_PID: u16 = 0x0CE6;
+pub const DUALSENSE_EDGE_PID: u16 = 0x0DF2;
+pub const DUALSENSE_ACCESS_PID: u16 = 0x0F08;
 
 pub const DS_INPUT_REPORT_USB: u8 = 0x01;
 pub const DS_INPUT_REPORT_USB_SIZE: usize = 64;
 pub const DS_INPUT_REPORT_BT: u8 = 0x31;
 pub const DS_INPUT_REPORT_BT_SIZE: usize = 78;
+/// Reduced report (id 0x01) sent over Bluetooth until the full mode is enabled
+pub const DS_INPUT_REPORT_BT_NONFULL_SIZE: usize = 10;
 
+/// Calibration report, reading it switches Bluetooth connections to full input reports
 pub const DS_FEATURE_REPORT_BT_FULL: u8 = 0x05;
+pub const DS_FEATURE_REPORT_BT_FULL_SIZE: usize = 41;
+
+pub const DS_FEATURE_REPORT_FIRMWARE_INFO: u8 = 0x20;
+pub const DS_FEATURE_REPORT_FIRMWARE_INFO_SIZE: usize = 64;
+
+pub const DS_FEATURE_REPORT_PAIRING_INFO: u8 = 0x09;
+pub const DS_FEATURE_REPORT_PAIRING_INFO_SIZE: usize = 20;
+
+pub const DS_OUTPUT_REPORT_USB: u8 = 0x02;
+pub const DS_OUTPUT_REPORT_USB_SIZE: usize = 63;
+pub const DS_OUTPUT_REPORT_BT: u8 = 0x31;
+pub const DS_OUTPUT_REPORT_BT_SIZE: usize = 78;
+pub const DS_OUTPUT_TAG: u8 = 0x10;
+
+pub const DS_OUTPUT_VALID_FLAG0_COMPATIBLE_VIBRATION: u8 = 0x01;
+pub const DS_OUTPUT_VALID_FLAG0_HAPTICS_SELECT: u8 = 0x02;
+pub const DS_OUTPUT_VALID_FLAG0_RIGHT_TRIGGER_EFFECT: u8 = 0x04;
+pub const DS_OUTPUT_VALID_FLAG0_LEFT_TRIGGER_EFFECT: u8 = 0x08;
+pub const DS_OUTPUT_VALID_FLAG1_MIC_MUTE_LED_CONTROL_ENABLE: u8 = 0x01;
+pub const DS_OUTPUT_VALID_FLAG1_LIGHTBAR_CONTROL_ENABLE: u8 = 0x04;
+pub const DS_OUTPUT_VALID_FLAG1_PLAYER_INDICATOR_CONTROL_ENABLE: u8 = 0x10;
+
+bitflags! {
+    /// Output report fields the controller applies, byte `n` of the bits is `valid_flag<n>`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct OutputFeatures: u32 {
+        const COMPATIBLE_VIBRATION = DS_OUTPUT_VALID_FLAG0_COMPATIBLE_VIBRATION as u32;
+        const HAPTICS_SELECT = DS_OUTPUT_VALID_FLAG0_HAPTICS_SELECT as u32;
+        const RIGHT_TRIGGER_EFFECT = DS_OUTPUT_VALID_FLAG0_RIGHT_TRIGGER_EFFECT as u32;
+        const LEFT_TRIGGER_EFFECT = DS_OUTPUT_VALID_FLAG0_LEFT_TRIGGER_EFFECT as u32;
+        const HEADPHONE_VOLUME = 0x10;
+        const SPEAKER_VOLUME = 0x20;
+        const MIC_VOLUME = 0x40;
+        const MIC_LED = (DS_OUTPUT_VALID_FLAG1_MIC_MUTE_LED_CONTROL_ENABLE as u32) << 8;
+        /// Power save control, which can turn off the touchpad and motion sensors
+        const TOUCHPAD_POWER = 0x02 << 8;
+        const LIGHTBAR = (DS_OUTPUT_VALID_FLAG1_LIGHTBAR_CONTROL_ENABLE as u32) << 8;
+        const PLAYER_INDICATOR_ENABLE =
+            (DS_OUTPUT_VALID_FLAG1_PLAYER_INDICATOR_CONTROL_ENABLE as u32) << 8;
+        const LIGHTBAR_SETUP = 0x02 << 16;
+        const COMPATIBLE_VIBRATION2 = 0x04 << 16;
+    }
+}
+
+pub const DS_PLAYER_LEDS_MASK: u8 = 0x1F;
+
+pub const DS_TRIGGER_EFFECT_OFF: u8 = 0x00;
+pub const DS_TRIGGER_EFFECT_SECTION: u8 = 0x02;
+
+pub const DS_INPUT_CRC32_SEED: u8 = 0xA1;
+pub const DS_OUTPUT_CRC32_SEED: u8 = 0xA2;
 
 pub const DS_STATUS_BATTERY_CAPACITY: u8 = 0xF;
 pub const DS_STATUS_CHARGING: u8 = 0xF0;
 pub const DS_STATUS_CHARGING_SHIFT: u8 = 4;
+/// Values of the charging nibble, see hid-playstation
+pub const DS_CHARGING_STATUS_DISCHARGING: u8 = 0x0;
+pub const DS_CHARGING_STATUS_CHARGING: u8 = 0x1;
+pub const DS_CHARGING_STATUS_FULL: u8 = 0x2;
+pub const DS_CHARGING_STATUS_VOLTAGE_ERROR: u8 = 0xA;
+pub const DS_CHARGING_STATUS_TEMPERATURE_ERROR: u8 = 0xB;
+pub const DS_CHARGING_STATUS_ERROR: u8 = 0xF;
 
-#[derive(FromBytes, KnownLayout, Immutable, PartialEq, Eq, Clone, Debug)]
+pub const DS_BUTTONS0_DPAD: u8 = 0x0F;
+pub const DS_DPAD_NEUTRAL: u8 = 0x08;
+
+pub const DS_TOUCH_POINT_INACTIVE: u8 = 0x80;
+pub const DS_TOUCH_POINT_ID: u8 = 0x7F;
+
+pub const DS_TOUCHPAD_WIDTH: u16 = 1920;
+pub const DS_TOUCHPAD_HEIGHT: u16 = 1080;
+
+/// Layout of `DualSenseInputReport::to_feature_vec`
+pub const FEAT_LEFT_STICK_X: usize = 0;
+pub const FEAT_LEFT_STICK_Y: usize = 1;
+pub const FEAT_RIGHT_STICK_X: usize = 2;
+pub const FEAT_RIGHT_STICK_Y: usize = 3;
+pub const FEAT_LEFT_TRIGGER: usize = 4;
+pub const FEAT_RIGHT_TRIGGER: usize = 5;
+/// First of the 15 buttons, in the order of `Button::ALL`
+pub const FEAT_BUTTONS: usize = 6;
+/// 21 features, padded with zeros
+pub const FEATURE_VEC_LEN: usize = 24;
+
+pub const DS_STICK_CENTER: f32 = 128.0;
+pub const DS_STICK_RANGE: f32 = 127.0;
+
+/// Nominal gyroscope sensitivity, ±2000 deg/s over the i16 range. The calibration report
+/// has the exact per-unit values.
+pub const DS_GYRO_COUNTS_PER_DEG_S: f32 = 32768.0 / 2000.0;
+/// `sensor_timestamp` ticks per microsecond
+pub const DS_SENSOR_TIMESTAMP_TICKS_PER_US: u32 = 3;
+/// Accelerometer counts per g, nominal and after calibration
+pub const DS_ACCEL_COUNTS_PER_G: f32 = 8192.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    Square,
+    Cross,
+    Circle,
+    Triangle,
+    L1,
+    R1,
+    L2,
+    R2,
+    Create,
+    Options,
+    L3,
+    R3,
+    PS,
+    Touchpad,
+    Mute,
+}
+
+impl Button {
+    pub const ALL: [Button; 15] = [
+        Self::Square,
+        Self::Cross,
+        Self::Circle,
+        Self::Triangle,
+        Self::L1,
+        Self::R1,
+        Self::L2,
+        Self::R2,
+        Self::Create,
+        Self::Options,
+        Self::L3,
+        Self::R3,
+        Self::PS,
+        Self::Touchpad,
+        Self::Mute,
+    ];
+
+    /// Index in the `buttons` array and bit mask of the button
+    fn mask(self) -> (usize, u8) {
+        match self {
+            Self::Square => (0, 0x10),
+            Self::Cross => (0, 0x20),
+            Self::Circle => (0, 0x40),
+            Self::Triangle => (0, 0x80),
+            Self::L1 => (1, 0x01),
+            Self::R1 => (1, 0x02),
+            Self::L2 => (1, 0x04),
+            Self::R2 => (1, 0x08),
+            Self::Create => (1, 0x10),
+            Self::Options => (1, 0x20),
+            Self::L3 => (1, 0x40),
+            Self::R3 => (1, 0x80),
+            Self::PS => (2, 0x01),
+            Self::Touchpad => (2, 0x02),
+            Self::Mute => (2, 0x04),
+        }
+    }
+}
+
+impl core::fmt::Display for Button {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self, f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DPad {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+    Neutral,
+}
+
+impl DPad {
+    fn from_hat(hat: u8) -> Self {
+        match hat {
+            0 => Self::Up,
+            1 => Self::UpRight,
+            2 => Self::Right,
+            3 => Self::DownRight,
+            4 => Self::Down,
+            5 => Self::DownLeft,
+            6 => Self::Left,
+            7 => Self::UpLeft,
+            _ => Self::Neutral,
+        }
+    }
+
+    fn to_hat(self) -> u8 {
+        match self {
+            Self::Up => 0,
+            Self::UpRight => 1,
+            Self::Right => 2,
+            Self::DownRight => 3,
+            Self::Down => 4,
+            Self::DownLeft => 5,
+            Self::Left => 6,
+            Self::UpLeft => 7,
+            Self::Neutral => DS_DPAD_NEUTRAL,
+        }
+    }
+
+    pub fn up(&self) -> bool {
+        matches!(self, Self::UpLeft | Self::Up | Self::UpRight)
+    }
+    pub fn down(&self) -> bool {
+        matches!(self, Self::DownLeft | Self::Down | Self::DownRight)
+    }
+    pub fn left(&self) -> bool {
+        matches!(self, Self::UpLeft | Self::Left | Self::DownLeft)
+    }
+    pub fn right(&self) -> bool {
+        matches!(self, Self::UpRight | Self::Right | Self::DownRight)
+    }
+}
+
+impl core::fmt::Display for DPad {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// The charging nibble of the status byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChargingStatus {
+    Discharging,
+    Charging,
+    Full,
+    /// Voltage or temperature out of range, charging paused
+    VoltageError,
+    TemperatureError,
+    Error,
+    Unknown(u8),
+}
+
+impl ChargingStatus {
+    pub fn from_nibble(nibble: u8) -> Self {
+        match nibble {
+            DS_CHARGING_STATUS_DISCHARGING => Self::Discharging,
+            DS_CHARGING_STATUS_CHARGING => Self::Charging,
+            DS_CHARGING_STATUS_FULL => Self::Full,
+            DS_CHARGING_STATUS_VOLTAGE_ERROR => Self::VoltageError,
+            DS_CHARGING_STATUS_TEMPERATURE_ERROR => Self::TemperatureError,
+            DS_CHARGING_STATUS_ERROR => Self::Error,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+/// Where the controller draws power from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChargingSource {
+    /// The USB-C port, which is also what the charging station plugs into. The controller
+    /// has no wireless charging, so there is no other source to report.
+    USB,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DualSenseModel {
+    DualSense,
+    Edge,
+    /// The Access Controller speaks the DualSense protocol, but its buttons are remappable.
+    ///
+    /// Every physical button reports the DualSense button it is assigned in the active
+    /// profile, so `buttons` reflects the logical controls rather than fixed positions.
+    /// The analog stick is reported as the left stick (`x`, `y`), and as there are no
+    /// analog triggers `z` and `rz` only take their rest and fully pressed values when
+    /// L2/R2 are assigned to a button. Touchpad and motion sensor fields are unused.
+    Access,
+}
+
+impl DualSenseModel {
+    pub fn from_product_id(product_id: u16) -> Option<Self> {
+        match product_id {
+            DUALSENSE_PID => Some(Self::DualSense),
+            DUALSENSE_EDGE_PID => Some(Self::Edge),
+            DUALSENSE_ACCESS_PID => Some(Self::Access),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a HID device with these ids speaks the DualSense protocol
+pub fn is_dualsense(vendor_id: u16, product_id: u16) -> bool {
+    vendor_id == SONY_VID && DualSenseModel::from_product_id(product_id).is_some()
+}
+
+impl core::fmt::Display for DualSenseModel {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::DualSense => write!(f, "DualSense"),
+            Self::Edge => write!(f, "DualSense Edge"),
+            Self::Access => write!(f, "Access Controller"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeadzoneShape {
+    /// Zero the stick when its magnitude is within the threshold
+    #[default]
+    Circular,
+    /// Zero each axis independently
+    Square,
+    /// Zero only the minor axis, keeping straight movements straight
+    Cross,
+}
+
+impl DeadzoneShape {
+    /// Applies the deadzone to a normalized stick position.
+    ///
+    /// `Circular` and `Square` rescale the remaining range so the output still reaches 1.0.
+    /// `threshold` is clamped to `0.0..1.0`, a deadzone covering the whole range would leave
+    /// nothing to rescale.
+    pub fn apply(&self, (x, y): (f32, f32), threshold: f32) -> (f32, f32) {
+        let threshold = threshold.clamp(0.0, 1.0 - f32::EPSILON);
+        let rescale = |v: f32| {
+            if v.abs() < threshold {
+                0.0
+            } else {
+                v.signum() * (v.abs() - threshold) / (1.0 - threshold)
+            }
+        };
+        match self {
+            Self::Circular => {
+                let magnitude = x.hypot(y);
+                // also a centered stick with no deadzone, which would divide 0 by 0
+                if magnitude < threshold || magnitude == 0.0 {
+                    return (0.0, 0.0);
+                }
+                let scale = rescale(magnitude.min(1.0)) / magnitude;
+                (x * scale, y * scale)
+            }
+            Self::Square => (rescale(x), rescale(y)),
+            Self::Cross => {
+                if x.abs() < y.abs() && x.abs() < threshold {
+                    (0.0, y)
+                } else if y.abs() <= x.abs() && y.abs() < threshold {
+                    (x, 0.0)
+                } else {
+                    (x, y)
+                }
+            }
+        }
+    }
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, PartialEq, Eq, Hash, Clone, Debug)]
 #[repr(C)]
 pub struct DualSenseTouchPoint {
     contact: u8,
@@ -26,19 +379,109 @@ pub struct DualSenseTouchPoint {
 }
 
 impl DualSenseTouchPoint {
+    /// A point with tracking `id` (7 bits) at `x`, `y` in touchpad units (12 bits each)
+    pub fn new(id: u8, x: u16, y: u16, active: bool) -> Self {
+        let inactive = if active { 0 } else { DS_TOUCH_POINT_INACTIVE };
+        Self {
+            contact: inactive | (id & DS_TOUCH_POINT_ID),
+            x_lo: x as u8,
+            xhi_ylo: (((x >> 8) as u8 & 0x0F) << 4) | (y as u8 & 0x0F),
+            y_hi: (y >> 4) as u8,
+        }
+    }
+
+    /// The 4 bytes as laid out in the input report
+    pub fn to_bytes(&self) -> [u8; 4] {
+        zerocopy::transmute!(self.clone())
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.contact & DS_TOUCH_POINT_INACTIVE == 0
+    }
+
+    /// Tracking id of the finger, incremented on every new touch
+    pub fn id(&self) -> u8 {
+        self.contact & DS_TOUCH_POINT_ID
+    }
+
     pub fn x(&self) -> u16 {
+        let x = self.raw_x();
+        debug_assert!(x < DS_TOUCHPAD_WIDTH, "touch point x out of bounds: {x}");
+        x
+    }
+    pub fn y(&self) -> u16 {
+        let y = self.raw_y();
+        debug_assert!(y < DS_TOUCHPAD_HEIGHT, "touch point y out of bounds: {y}");
+        y
+    }
+
+    /// Position in touchpad units, `None` while not touching.
+    ///
+    /// The controller reports whole units on a 1920x1080 grid (12 bits per axis), so the
+    /// values currently have no fractional part.
+    pub fn position_f32(&self) -> Option<(f32, f32)> {
+        self.is_active()
+            .then(|| (self.raw_x() as f32, self.raw_y() as f32))
+    }
+
+    /// Position in `0.0..1.0` of the touchpad width and height, with the same precision
+    /// limit as `position_f32`.
+    pub fn position_normalized_f64(&self) -> Option<(f64, f64)> {
+        self.is_active().then(|| {
+            (
+                self.raw_x() as f64 / DS_TOUCHPAD_WIDTH as f64,
+                self.raw_y() as f64 / DS_TOUCHPAD_HEIGHT as f64,
+            )
+        })
+    }
+
+    /// Euclidean distance in touchpad units, `None` unless both points are touching
+    pub fn distance_to(&self, other: &Self) -> Option<f32> {
+        let (x1, y1) = self.position_f32()?;
+        let (x2, y2) = other.position_f32()?;
+        Some((x2 - x1).hypot(y2 - y1))
+    }
+
+    /// Center between both points in touchpad units, `None` unless both are touching
+    pub fn midpoint(&self, other: &Self) -> Option<(f32, f32)> {
+        let (x1, y1) = self.position_f32()?;
+        let (x2, y2) = other.position_f32()?;
+        Some(((x1 + x2) / 2.0, (y1 + y2) / 2.0))
+    }
+
+    /// Checks the coordinates are within the touchpad, to filter out corrupt touch data.
+    pub fn is_valid(&self) -> bool {
+        self.raw_x() < DS_TOUCHPAD_WIDTH && self.raw_y() < DS_TOUCHPAD_HEIGHT
+    }
+
+    fn raw_x(&self) -> u16 {
         let x_hi = (self.xhi_ylo >> 4) as u16;
         let x_lo = self.x_lo as u16;
         (x_hi << 8) | x_lo
     }
-    pub fn y(&self) -> u16 {
+    fn raw_y(&self) -> u16 {
         let y_lo = (self.xhi_ylo & 0x0F) as u16;
         let y_hi = self.y_hi as u16;
         (y_hi << 4) | y_lo
     }
 }
 
-#[derive(FromBytes, KnownLayout, Immutable, PartialEq, Eq, Clone, Debug)]
+/// Active points first, then by tracking id. The coordinates only break ties so the
+/// ordering stays consistent with `Eq`.
+impl Ord for DualSenseTouchPoint {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        let key = |point: &Self| (!point.is_active(), point.id(), point.raw_x(), point.raw_y());
+        key(self).cmp(&key(other))
+    }
+}
+
+impl PartialOrd for DualSenseTouchPoint {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, PartialEq, Eq, Clone, Debug)]
 #[repr(C)]
 pub struct DualSenseInputReport {
     x: u8,
@@ -64,8 +507,24 @@ pub struct DualSenseInputReport {
     reserved4: [u8; 10],
 }
 pub const DS_INPUT_REPORT_SIZE: usize = core::mem::size_of::<DualSenseInputReport>();
+// parse() skips the report id, and on BT the byte after it. BT reports are followed by 9
+// reserved bytes and the CRC32, so only USB is an exact fit.
+const _: () = assert!(DS_INPUT_REPORT_USB_SIZE - 1 == DS_INPUT_REPORT_SIZE);
+const _: () = assert!(DS_INPUT_REPORT_BT_SIZE - 2 - 13 == DS_INPUT_REPORT_SIZE);
 
 impl DualSenseInputReport {
+    /// Views a USB or Bluetooth input report, including its report id, without copying.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use dualsense_tray::dualsense::proto::{Button, DualSenseInputReport};
+    /// let mut buf = [0u8; 64];
+    /// buf[0] = 0x01; // USB report
+    /// buf[8] = 0x28; // Cross pressed, d-pad neutral
+    /// let report = DualSenseInputReport::parse(&buf).unwrap();
+    /// assert!(report.is_pressed(Button::Cross));
+    /// ```
     pub fn parse<'a>(data: &'a [u8]) -> Option<&'a Self> {
         let offset = match *data.first()? {
             DS_INPUT_REPORT_USB => 1,
@@ -76,12 +535,409 @@ impl DualSenseInputReport {
         Self::ref_from_bytes(bytes).ok()
     }
 
+    /// Raw USB input report with its report id, the inverse of `parse`
+    pub fn to_usb_bytes(&self) -> [u8; DS_INPUT_REPORT_USB_SIZE] {
+        encode_usb(self)
+    }
+
+    /// Raw Bluetooth full input report with its report id and CRC32, the inverse of `parse`
+    pub fn to_bt_bytes(&self) -> [u8; DS_INPUT_REPORT_BT_SIZE] {
+        encode_bt(self)
+    }
+
     pub fn battery(&self) -> (u8, bool) {
         let s = self.status;
         let capacity = s & DS_STATUS_BATTERY_CAPACITY;
         let charging = (s & DS_STATUS_CHARGING) >> DS_STATUS_CHARGING_SHIFT;
         (capacity * 10, charging == 1)
     }
+
+    pub fn charging_status(&self) -> ChargingStatus {
+        ChargingStatus::from_nibble((self.status & DS_STATUS_CHARGING) >> DS_STATUS_CHARGING_SHIFT)
+    }
+
+    /// Whether external power is present, also while full or when charging stopped on an
+    /// error, none of which `battery()` reports as charging.
+    pub fn is_connected_to_power(&self) -> bool {
+        self.charging_status() != ChargingStatus::Discharging
+    }
+
+    pub fn charging_source(&self) -> Option<ChargingSource> {
+        self.is_connected_to_power().then_some(ChargingSource::USB)
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        let (index, mask) = button.mask();
+        self.buttons[index] & mask != 0
+    }
+
+    /// Whether touch point slot `finger` (0 or 1) is in contact, other slots never are.
+    pub fn is_touchpad_finger_down(&self, finger: u8) -> bool {
+        self.points
+            .get(finger as usize)
+            .is_some_and(DualSenseTouchPoint::is_active)
+    }
+
+    /// The touchpad click, independent of any finger being tracked
+    pub fn touchpad_button_pressed(&self) -> bool {
+        self.is_pressed(Button::Touchpad)
+    }
+
+    pub fn is_mute_button_pressed(&self) -> bool {
+        self.is_pressed(Button::Mute)
+    }
+
+    pub fn pressed_buttons(&self) -> Vec<Button> {
+        Button::ALL
+            .into_iter()
+            .filter(|button| self.is_pressed(*button))
+            .collect()
+    }
+
+    pub fn dpad(&self) -> DPad {
+        DPad::from_hat(self.buttons[0] & DS_BUTTONS0_DPAD)
+    }
+
+    /// `(newly_pressed, newly_released)` buttons since `prev`, the d-pad is reported by
+    /// `dpad_changed`. Nothing is allocated when no button changed.
+    pub fn buttons_changed(&self, prev: &Self) -> (Vec<Button>, Vec<Button>) {
+        let mut changed = [0u8; 4];
+        for (i, byte) in changed.iter_mut().enumerate() {
+            *byte = self.buttons[i] ^ prev.buttons[i];
+        }
+        changed[0] &= !DS_BUTTONS0_DPAD;
+        if changed == [0; 4] {
+            return (Vec::new(), Vec::new());
+        }
+
+        Button::ALL
+            .into_iter()
+            .filter(|button| {
+                let (index, mask) = button.mask();
+                changed[index] & mask != 0
+            })
+            .partition(|button| self.is_pressed(*button))
+    }
+
+    /// The new d-pad direction, if it changed since `prev`
+    pub fn dpad_changed(&self, prev: &Self) -> Option<DPad> {
+        let dpad = self.dpad();
+        (dpad != prev.dpad()).then_some(dpad)
+    }
+
+    /// Left trigger travel in `0.0..=1.0`
+    pub fn left_trigger(&self) -> f32 {
+        self.z as f32 / u8::MAX as f32
+    }
+
+    /// Right trigger travel in `0.0..=1.0`
+    pub fn right_trigger(&self) -> f32 {
+        self.rz as f32 / u8::MAX as f32
+    }
+
+    /// Left trigger travel together with whether it is inside the resistance zone of
+    /// `effect`. The controller doesn't report its active effect, so it has to be the one
+    /// last written, `None` if unknown.
+    pub fn left_trigger_state(&self, effect: Option<&TriggerEffect>) -> TriggerState {
+        TriggerState::new(self.z, effect)
+    }
+
+    pub fn right_trigger_state(&self, effect: Option<&TriggerEffect>) -> TriggerState {
+        TriggerState::new(self.rz, effect)
+    }
+
+    /// Left stick as `(x, y)` in `-1.0..=1.0`, with positive `y` pointing up.
+    pub fn left_stick(&self) -> (f32, f32) {
+        normalize_stick(self.x, self.y)
+    }
+
+    /// Right stick as `(x, y)` in `-1.0..=1.0`, with positive `y` pointing up.
+    pub fn right_stick(&self) -> (f32, f32) {
+        normalize_stick(self.rx, self.ry)
+    }
+
+    pub fn left_stick_with_deadzone(&self, threshold: f32, shape: DeadzoneShape) -> (f32, f32) {
+        shape.apply(self.left_stick(), threshold)
+    }
+
+    pub fn right_stick_with_deadzone(&self, threshold: f32, shape: DeadzoneShape) -> (f32, f32) {
+        shape.apply(self.right_stick(), threshold)
+    }
+
+    /// Left stick with the calibrated resting position subtracted before normalizing to the
+    /// learned range.
+    pub fn left_stick_calibrated(&self, cal: &StickCalibrator) -> (f32, f32) {
+        cal.left_stick(self.x, self.y)
+    }
+
+    /// Right stick with the calibrated resting position subtracted before normalizing to the
+    /// learned range.
+    pub fn right_stick_calibrated(&self, cal: &StickCalibrator) -> (f32, f32) {
+        cal.right_stick(self.rx, self.ry)
+    }
+
+    /// Touch points sorted by tracking id with inactive slots last, so a finger keeps its
+    /// position across reports even if the controller moves it to another slot.
+    pub fn sorted_touch_points(&self) -> [&DualSenseTouchPoint; 2] {
+        let [a, b] = &self.points;
+        if a <= b { [a, b] } else { [b, a] }
+    }
+
+    /// Left stick passed through `filter`, which keeps the history between reports.
+    pub fn left_stick_smoothed(&self, filter: &mut SmoothingFilter) -> (f32, f32) {
+        filter.apply(self.left_stick())
+    }
+
+    pub fn right_stick_smoothed(&self, filter: &mut SmoothingFilter) -> (f32, f32) {
+        filter.apply(self.right_stick())
+    }
+
+    /// Left stick passed through a Kalman `filter`, which keeps its state between reports.
+    pub fn left_stick_kalman(&self, filter: &mut StickKalmanFilter) -> (f32, f32) {
+        filter.update(self.left_stick())
+    }
+
+    pub fn right_stick_kalman(&self, filter: &mut StickKalmanFilter) -> (f32, f32) {
+        filter.update(self.right_stick())
+    }
+
+    /// Sticks, triggers and buttons as a flat vector, e.g. as the input of a neural network.
+    /// See the `FEAT_*` constants for the indices.
+    pub fn to_feature_vec(&self) -> [f32; FEATURE_VEC_LEN] {
+        let mut features = [0.0; FEATURE_VEC_LEN];
+        (features[FEAT_LEFT_STICK_X], features[FEAT_LEFT_STICK_Y]) = self.left_stick();
+        (features[FEAT_RIGHT_STICK_X], features[FEAT_RIGHT_STICK_Y]) = self.right_stick();
+        features[FEAT_LEFT_TRIGGER] = self.left_trigger();
+        features[FEAT_RIGHT_TRIGGER] = self.right_trigger();
+        for (i, button) in Button::ALL.into_iter().enumerate() {
+            features[FEAT_BUTTONS + i] = if self.is_pressed(button) { 1.0 } else { 0.0 };
+        }
+        features
+    }
+
+    /// Counter incremented on every report, wrapping around
+    pub fn seq_number(&self) -> u8 {
+        self.seq_number
+    }
+
+    /// Gyroscope readings before any calibration or scaling
+    pub fn raw_gyro(&self) -> [u16; 3] {
+        self.gyro.map(|v| v.get())
+    }
+
+    /// Accelerometer readings before any calibration or scaling
+    pub fn raw_accel(&self) -> [u16; 3] {
+        self.accel.map(|v| v.get())
+    }
+
+    /// Angular rate in degrees per second, from the nominal sensitivity
+    pub fn gyro_dps(&self) -> [f32; 3] {
+        self.raw_gyro()
+            .map(|v| v as i16 as f32 / DS_GYRO_COUNTS_PER_DEG_S)
+    }
+
+    /// Acceleration in g, from the nominal sensitivity
+    pub fn accel_g(&self) -> [f32; 3] {
+        self.raw_accel()
+            .map(|v| v as i16 as f32 / DS_ACCEL_COUNTS_PER_G)
+    }
+
+    /// Gyroscope, accelerometer and sensor timestamp in one go, calibrated when
+    /// `calibration` is given and nominal otherwise.
+    pub fn imu_data(&self, calibration: Option<&ImuCalibration>) -> ImuData {
+        let (gyro, accel) = match calibration {
+            Some(calibration) => (
+                calibration.gyro_dps(self.raw_gyro()),
+                calibration.accel_g(self.raw_accel()),
+            ),
+            None => (self.gyro_dps(), self.accel_g()),
+        };
+        ImuData {
+            gyro,
+            accel,
+            timestamp_us: (self.sensor_timestamp.get() / DS_SENSOR_TIMESTAMP_TICKS_PER_US) as u64,
+        }
+    }
+
+    /// Sensor time since `prev` was sampled, the timestamp wraps around every ~24 minutes
+    pub fn timestamp_elapsed_us(&self, prev: &Self) -> u64 {
+        let ticks = self
+            .sensor_timestamp
+            .get()
+            .wrapping_sub(prev.sensor_timestamp.get());
+        ticks.div_ceil(DS_SENSOR_TIMESTAMP_TICKS_PER_US) as u64
+    }
+
+    /// Rotation in degrees around each axis since `prev`, `dt_us` apart. The rate is
+    /// averaged between both reports.
+    pub fn gyro_delta(&self, prev: &Self, dt_us: u64) -> [f32; 3] {
+        let dt = dt_us as f32 / 1_000_000.0;
+        let (rate, prev_rate) = (self.gyro_dps(), prev.gyro_dps());
+        core::array::from_fn(|i| (rate[i] + prev_rate[i]) / 2.0 * dt)
+    }
+
+    /// The four button bytes as sent by the controller, d-pad hat in the low nibble of the first
+    pub fn buttons_raw(&self) -> [u8; 4] {
+        self.buttons
+    }
+
+    /// Battery capacity in the low nibble, charging state in the high nibble
+    pub fn status_raw(&self) -> u8 {
+        self.status
+    }
+
+    /// `(x, y, rx, ry)` as sent by the controller, centered at 0x80 with Y growing downwards
+    pub fn sticks_raw(&self) -> (u8, u8, u8, u8) {
+        (self.x, self.y, self.rx, self.ry)
+    }
+
+    /// `(z, rz)` trigger travel as sent by the controller
+    pub fn triggers_raw(&self) -> (u8, u8) {
+        (self.z, self.rz)
+    }
+
+    pub(super) fn has_button_input(&self) -> bool {
+        let [b0, b1, b2, _] = self.buttons;
+        (b0 & DS_BUTTONS0_DPAD) != DS_DPAD_NEUTRAL
+            || (b0 & !DS_BUTTONS0_DPAD) != 0
+            || b1 != 0
+            || b2 != 0
+    }
+
+    /// Whether any face, shoulder, stick, PS or touchpad button is held, ignoring the d-pad.
+    #[inline]
+    pub fn is_any_button_pressed(&self) -> bool {
+        let [b0, b1, b2, _] = self.buttons;
+        ((b0 & 0xF0) | b1 | (b2 & 0x03)) != 0
+    }
+
+    /// Whether either stick is pushed beyond `deadzone`.
+    #[inline]
+    pub fn has_stick_input(&self, deadzone: f32) -> bool {
+        let (lx, ly) = self.left_stick();
+        let (rx, ry) = self.right_stick();
+        let deadzone = deadzone * deadzone;
+        ((lx * lx + ly * ly) > deadzone) | ((rx * rx + ry * ry) > deadzone)
+    }
+
+    /// Left stick as `(angle_radians, magnitude)`, angle measured from the positive X axis.
+    pub fn left_stick_polar(&self) -> (f32, f32) {
+        to_polar(self.left_stick())
+    }
+
+    /// Right stick as `(angle_radians, magnitude)`, angle measured from the positive X axis.
+    pub fn right_stick_polar(&self) -> (f32, f32) {
+        to_polar(self.right_stick())
+    }
+}
+
+/// Decoded values, meant to be pasted in bug reports
+impl core::fmt::Display for DualSenseInputReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let (lx, ly) = self.left_stick();
+        let (rx, ry) = self.right_stick();
+        write!(f, "Sticks: L({lx:.2}, {ly:.2}) R({rx:.2}, {ry:.2})")?;
+        write!(
+            f,
+            " | Triggers: L={:.2} R={:.2}",
+            self.left_trigger(),
+            self.right_trigger()
+        )?;
+
+        let buttons = self.pressed_buttons();
+        if buttons.is_empty() {
+            write!(f, " | Buttons: none")?;
+        } else {
+            let buttons = buttons.iter().map(Button::to_string).collect::<Vec<_>>();
+            write!(f, " | Buttons: {}", buttons.join(", "))?;
+        }
+        write!(f, " | DPad: {}", self.dpad())?;
+
+        let (capacity, charging) = self.battery();
+        write!(f, " | Battery: {capacity}%")?;
+        if charging {
+            write!(f, " charging")?;
+        }
+
+        let touches = self.points.iter().filter(|point| point.is_active());
+        write!(f, " | Touch:")?;
+        let mut any_touch = false;
+        for point in touches {
+            any_touch = true;
+            write!(f, " #{}({}, {})", point.id(), point.raw_x(), point.raw_y())?;
+        }
+        if !any_touch {
+            write!(f, " none")?;
+        }
+        Ok(())
+    }
+}
+
+/// Blends two reports for consumers polling slower than the controller, `t` in `0.0..=1.0`.
+/// Sticks, triggers and motion sensors are interpolated linearly, everything else (buttons,
+/// d-pad, touch, battery) is taken from `a` below 0.5 and from `b` from 0.5 onwards.
+pub fn interpolate(
+    a: &DualSenseInputReport,
+    b: &DualSenseInputReport,
+    t: f32,
+) -> DualSenseInputReport {
+    let t = t.clamp(0.0, 1.0);
+    let lerp_u8 = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    // motion sensors are signed words
+    let lerp_i16 = |a: U16<LE>, b: U16<LE>| {
+        let (a, b) = (a.get() as i16 as f32, b.get() as i16 as f32);
+        U16::new((a + (b - a) * t).round() as i16 as u16)
+    };
+
+    let mut report = if t < 0.5 { a.clone() } else { b.clone() };
+    report.x = lerp_u8(a.x, b.x);
+    report.y = lerp_u8(a.y, b.y);
+    report.rx = lerp_u8(a.rx, b.rx);
+    report.ry = lerp_u8(a.ry, b.ry);
+    report.z = lerp_u8(a.z, b.z);
+    report.rz = lerp_u8(a.rz, b.rz);
+    for i in 0..3 {
+        report.gyro[i] = lerp_i16(a.gyro[i], b.gyro[i]);
+        report.accel[i] = lerp_i16(a.accel[i], b.accel[i]);
+    }
+    report
+}
+
+fn normalize_stick(x: u8, y: u8) -> (f32, f32) {
+    normalize_stick_around(x, y, (DS_STICK_CENTER, DS_STICK_CENTER))
+}
+
+fn normalize_stick_around(x: u8, y: u8, (cx, cy): (f32, f32)) -> (f32, f32) {
+    let x = (x as f32 - cx) / DS_STICK_RANGE;
+    // HID reports Y growing downwards
+    let y = (cy - y as f32) / DS_STICK_RANGE;
+    (x.clamp(-1.0, 1.0), y.clamp(-1.0, 1.0))
+}
+
+fn to_polar((x, y): (f32, f32)) -> (f32, f32) {
+    let angle = f32::atan2(y, x);
+    // diagonals can exceed the circular range, the magnitude is clamped afterwards
+    let magnitude = x.hypot(y).min(1.0);
+    (angle, magnitude)
+}
+
+/// Raw USB input report carrying `report`, the inverse of `DualSenseInputReport::parse`
+pub fn encode_usb(report: &DualSenseInputReport) -> [u8; DS_INPUT_REPORT_USB_SIZE] {
+    let mut buf = [0u8; DS_INPUT_REPORT_USB_SIZE];
+    buf[0] = DS_INPUT_REPORT_USB;
+    buf[1..].copy_from_slice(report.as_bytes());
+    buf
+}
+
+/// Raw Bluetooth full input report carrying `report`, with a valid trailing CRC32
+pub fn encode_bt(report: &DualSenseInputReport) -> [u8; DS_INPUT_REPORT_BT_SIZE] {
+    let mut buf = [0u8; DS_INPUT_REPORT_BT_SIZE];
+    buf[0] = DS_INPUT_REPORT_BT;
+    buf[2..2 + DS_INPUT_REPORT_SIZE].copy_from_slice(report.as_bytes());
+    let crc_offset = DS_INPUT_REPORT_BT_SIZE - 4;
+    let crc = ds_crc32(DS_INPUT_CRC32_SEED, &buf[..crc_offset]);
+    buf[crc_offset..].copy_from_slice(&crc.to_le_bytes());
+    buf
 }
 
 #[derive(FromBytes, KnownLayout, Immutable, PartialEq, Eq, Clone, Debug)]
@@ -96,10 +952,8 @@ pub struct DualSenseInputReportUSB {
      */
     pub padding: [u8; 14],
 }
-const_assert_eq!(
-    core::mem::size_of::<DualSenseInputReportUSB>(),
-    DS_INPUT_REPORT_BT_SIZE
-);
+const _: () = assert!(core::mem::size_of::<DualSenseInputReportUSB>() == DS_INPUT_REPORT_BT_SIZE);
+const _: () = assert!(core::mem::offset_of!(DualSenseInputReportUSB, input_report) == 1);
 
 #[derive(FromBytes, KnownLayout, Immutable, PartialEq, Eq, Clone, Debug)]
 #[repr(C)]
@@ -109,7 +963,1304 @@ pub struct DualSenseInputReportBT {
     pub input_report: DualSenseInputReport,
     pub padding2: [u8; 13],
 }
-const_assert_eq!(
-    core::mem::size_of::<DualSenseInputReportBT>(),
-    DS_INPUT_REPORT_BT_SIZE
-);
+const _: () = assert!(core::mem::size_of::<DualSenseInputReportBT>() == DS_INPUT_REPORT_BT_SIZE);
+const _: () = assert!(core::mem::offset_of!(DualSenseInputReportBT, input_report) == 2);
+
+/// Output report fields shared by USB and Bluetooth, only the fields whose valid flag is set
+/// are applied by the controller.
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, PartialEq, Eq, Clone, Debug)]
+#[repr(C)]
+pub struct DualSenseOutputReport {
+    valid_flag0: u8,
+    valid_flag1: u8,
+
+    motor_right: u8, // weak, high frequency
+    motor_left: u8,  // strong, low frequency
+
+    headphone_volume: u8,
+    speaker_volume: u8,
+    mic_volume: u8,
+    audio_control: u8,
+    mute_button_led: u8,
+    power_save_control: u8,
+
+    right_trigger_effect: [u8; 11],
+    left_trigger_effect: [u8; 11],
+    reserved: [u8; 6],
+
+    valid_flag2: u8,
+    reserved2: [u8; 2],
+    lightbar_setup: u8,
+    led_brightness: u8,
+    player_leds: u8,
+    lightbar_red: u8,
+    lightbar_green: u8,
+    lightbar_blue: u8,
+}
+const _: () = assert!(core::mem::size_of::<DualSenseOutputReport>() == 47);
+
+/// A report that leaves every feature unchanged. The report id is added by
+/// `to_usb_bytes`/`to_bt_bytes`, as it depends on the connection.
+impl Default for DualSenseOutputReport {
+    fn default() -> Self {
+        Self::new_zeroed()
+    }
+}
+
+impl DualSenseOutputReport {
+    /// Explicitly turns off rumble, trigger effects, the lightbar, the player LEDs and the
+    /// mic LED, to leave the controller in a clean state e.g. when exiting.
+    pub fn reset_all_features() -> Self {
+        let mut report = Self::default();
+        report.enable(
+            OutputFeatures::COMPATIBLE_VIBRATION
+                | OutputFeatures::HAPTICS_SELECT
+                | OutputFeatures::RIGHT_TRIGGER_EFFECT
+                | OutputFeatures::LEFT_TRIGGER_EFFECT
+                | OutputFeatures::MIC_LED
+                | OutputFeatures::LIGHTBAR
+                | OutputFeatures::PLAYER_INDICATOR_ENABLE,
+        );
+        report
+    }
+
+    /// Fields the controller will apply
+    pub fn features(&self) -> OutputFeatures {
+        OutputFeatures::from_bits_retain(u32::from_le_bytes([
+            self.valid_flag0,
+            self.valid_flag1,
+            self.valid_flag2,
+            0,
+        ]))
+    }
+
+    fn enable(&mut self, features: OutputFeatures) {
+        let [flag0, flag1, flag2, _] = features.bits().to_le_bytes();
+        self.valid_flag0 |= flag0;
+        self.valid_flag1 |= flag1;
+        self.valid_flag2 |= flag2;
+    }
+
+    /// `(strong, weak)` motor speeds
+    pub fn rumble(&self) -> (u8, u8) {
+        (self.motor_left, self.motor_right)
+    }
+
+    pub fn lightbar(&self) -> (u8, u8, u8) {
+        (self.lightbar_red, self.lightbar_green, self.lightbar_blue)
+    }
+
+    pub fn player_leds(&self) -> u8 {
+        self.player_leds
+    }
+
+    pub fn to_usb_bytes(&self) -> [u8; DS_OUTPUT_REPORT_USB_SIZE] {
+        let report = DualSenseOutputReportUSB {
+            report_id: DS_OUTPUT_REPORT_USB,
+            output_report: self.clone(),
+            padding: [0; 15],
+        };
+        zerocopy::transmute!(report)
+    }
+
+    /// `seq` is a 4 bit counter the controller uses to discard duplicated reports
+    pub fn to_bt_bytes(&self, seq: u8) -> [u8; DS_OUTPUT_REPORT_BT_SIZE] {
+        let mut report = DualSenseOutputReportBT {
+            report_id: DS_OUTPUT_REPORT_BT,
+            seq_tag: (seq & 0x0F) << 4,
+            tag: DS_OUTPUT_TAG,
+            output_report: self.clone(),
+            reserved: [0; 24],
+            crc32: U32::ZERO,
+        };
+        let crc = ds_crc32(
+            DS_OUTPUT_CRC32_SEED,
+            &report.as_bytes()[..DS_OUTPUT_REPORT_BT_SIZE - 4],
+        );
+        report.crc32 = U32::new(crc);
+        zerocopy::transmute!(report)
+    }
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, PartialEq, Eq, Clone, Debug)]
+#[repr(C)]
+pub struct DualSenseOutputReportUSB {
+    pub report_id: u8, // 0x02
+    pub output_report: DualSenseOutputReport,
+    pub padding: [u8; 15],
+}
+const _: () =
+    assert!(core::mem::size_of::<DualSenseOutputReportUSB>() == DS_OUTPUT_REPORT_USB_SIZE);
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, PartialEq, Eq, Clone, Debug)]
+#[repr(C)]
+pub struct DualSenseOutputReportBT {
+    pub report_id: u8, // 0x31
+    pub seq_tag: u8,
+    pub tag: u8,
+    pub output_report: DualSenseOutputReport,
+    pub reserved: [u8; 24],
+    pub crc32: U32<LE>,
+}
+const _: () = assert!(core::mem::size_of::<DualSenseOutputReportBT>() == DS_OUTPUT_REPORT_BT_SIZE);
+
+/// Adaptive trigger effect, serialized into the 11 byte effect block of each trigger
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TriggerEffect {
+    /// No resistance
+    #[default]
+    Off,
+    /// Resistance between two points of the trigger travel ("section" mode 0x02).
+    ///
+    /// Positions go from 0 at rest to 255 fully pulled, so `start_position = 0` resists from
+    /// the very beginning. `strength = 255` is the most rigid the motor can get.
+    PositionFeedback {
+        start_position: u8,
+        end_position: u8,
+        strength: u8,
+    },
+}
+
+impl TriggerEffect {
+    /// Whether the raw trigger `position` is where the effect pushes back
+    pub fn resists_at(&self, position: u8) -> bool {
+        match *self {
+            Self::Off => false,
+            Self::PositionFeedback {
+                start_position,
+                end_position,
+                ..
+            } => (start_position..=end_position).contains(&position),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 11] {
+        let mut bytes = [0; 11];
+        match *self {
+            Self::Off => bytes[0] = DS_TRIGGER_EFFECT_OFF,
+            Self::PositionFeedback {
+                start_position,
+                end_position,
+                strength,
+            } => {
+                bytes[0] = DS_TRIGGER_EFFECT_SECTION;
+                bytes[1] = start_position;
+                bytes[2] = end_position;
+                bytes[3] = strength;
+            }
+        }
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriggerState {
+    /// Trigger travel in `0.0..=1.0`
+    pub position: f32,
+    /// `None` when the effect is unknown
+    pub in_effect_zone: Option<bool>,
+}
+
+impl TriggerState {
+    fn new(raw: u8, effect: Option<&TriggerEffect>) -> Self {
+        Self {
+            position: raw as f32 / u8::MAX as f32,
+            in_effect_zone: effect.map(|effect| effect.resists_at(raw)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightbarEffect {
+    Off,
+    Static {
+        r: u8,
+        g: u8,
+        b: u8,
+    },
+    /// Fades the color in and out. The output report has no animation mode, so the
+    /// brightness has to be updated continuously with the frames from `color_at`.
+    ///
+    /// `rate` is in breaths per minute (1–255), higher is faster.
+    Pulse {
+        r: u8,
+        g: u8,
+        b: u8,
+        rate: u8,
+    },
+}
+
+impl LightbarEffect {
+    /// Color to show `elapsed` after the effect started
+    pub fn color_at(&self, elapsed: core::time::Duration) -> (u8, u8, u8) {
+        match *self {
+            Self::Off => (0, 0, 0),
+            Self::Static { r, g, b } => (r, g, b),
+            Self::Pulse { r, g, b, rate } => {
+                let period = 60.0 / rate.max(1) as f32;
+                let phase = (elapsed.as_secs_f32() % period) / period;
+                // starts dark, full brightness halfway through the period
+                let brightness = 0.5 - 0.5 * (phase * core::f32::consts::TAU).cos();
+                let scale = |c: u8| (c as f32 * brightness).round() as u8;
+                (scale(r), scale(g), scale(b))
+            }
+        }
+    }
+}
+
+/// Named lightbar colors, for picking a color without specifying RGB
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LightbarPreset {
+    BluePS,
+    RedAlert,
+    GreenHealth,
+    OrangeAmber,
+    WhiteNeutral,
+    PurpleSpooky,
+    /// Green, yellow or red depending on the battery level
+    BatteryLevel,
+}
+
+impl LightbarPreset {
+    pub const ALL: [LightbarPreset; 7] = [
+        Self::BluePS,
+        Self::RedAlert,
+        Self::GreenHealth,
+        Self::OrangeAmber,
+        Self::WhiteNeutral,
+        Self::PurpleSpooky,
+        Self::BatteryLevel,
+    ];
+}
+
+impl core::fmt::Display for LightbarPreset {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// RGB color of `preset`. `battery` is the capacity in percent, as returned by
+/// `DualSenseInputReport::battery`, and is only used by `BatteryLevel`, where an unknown
+/// level shows as medium.
+pub fn from_preset(preset: LightbarPreset, battery: Option<u8>) -> (u8, u8, u8) {
+    match preset {
+        LightbarPreset::BluePS => (0, 0, 128),
+        LightbarPreset::RedAlert => (255, 0, 0),
+        LightbarPreset::GreenHealth => (0, 255, 0),
+        LightbarPreset::OrangeAmber => (255, 128, 0),
+        LightbarPreset::WhiteNeutral => (255, 255, 255),
+        LightbarPreset::PurpleSpooky => (128, 0, 128),
+        LightbarPreset::BatteryLevel => match battery {
+            Some(60..) => (0, 255, 0),
+            Some(..30) => (255, 0, 0),
+            _ => (255, 255, 0),
+        },
+    }
+}
+
+/// Motion sensor readings of one report
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ImuData {
+    /// Degrees per second around the pitch, yaw and roll axes
+    pub gyro: [f32; 3],
+    pub accel: [f32; 3],
+    /// Sensor clock, wraps around every ~24 minutes
+    pub timestamp_us: u64,
+}
+
+/// IMU calibration from feature report 0x05, applied as in hid-playstation
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, PartialEq, Eq, Clone, Debug)]
+#[repr(C)]
+pub struct ImuCalibration {
+    report_id: u8,
+    /// Pitch, yaw and roll
+    gyro_bias: [I16<LE>; 3],
+    /// Plus and minus reading per axis, interleaved like the accelerometer ones
+    gyro_plus_minus: [[I16<LE>; 2]; 3],
+    gyro_speed_plus: I16<LE>,
+    gyro_speed_minus: I16<LE>,
+    accel_plus_minus: [[I16<LE>; 2]; 3],
+    reserved: [u8; 6],
+}
+const _: () = assert!(core::mem::size_of::<ImuCalibration>() == DS_FEATURE_REPORT_BT_FULL_SIZE);
+
+impl ImuCalibration {
+    pub fn parse(data: &[u8]) -> Option<&Self> {
+        if *data.first()? != DS_FEATURE_REPORT_BT_FULL {
+            return None;
+        }
+        let bytes = data.get(..DS_FEATURE_REPORT_BT_FULL_SIZE)?;
+        Self::ref_from_bytes(bytes).ok()
+    }
+
+    /// Axes with a degenerate calibration fall back to the nominal sensitivity
+    pub fn gyro_dps(&self, raw: [u16; 3]) -> [f32; 3] {
+        let speed_2x = self.gyro_speed_plus.get() as f32 + self.gyro_speed_minus.get() as f32;
+        core::array::from_fn(|i| {
+            let raw = raw[i] as i16 as f32;
+            let bias = self.gyro_bias[i].get() as f32;
+            let [plus, minus] = self.gyro_plus_minus[i].map(|v| v.get() as f32);
+            let range = (plus - bias).abs() + (minus - bias).abs();
+            if range == 0.0 {
+                return raw / DS_GYRO_COUNTS_PER_DEG_S;
+            }
+            (raw - bias) * speed_2x / range
+        })
+    }
+
+    pub fn accel_g(&self, raw: [u16; 3]) -> [f32; 3] {
+        core::array::from_fn(|i| {
+            let raw = raw[i] as i16 as f32;
+            let [plus, minus] = self.accel_plus_minus[i].map(|v| v.get() as f32);
+            let range_2g = plus - minus;
+            if range_2g == 0.0 {
+                return raw / DS_ACCEL_COUNTS_PER_G;
+            }
+            let bias = plus - range_2g / 2.0;
+            (raw - bias) * 2.0 / range_2g
+        })
+    }
+}
+
+/// Firmware info feature report 0x20, as read by the Linux hid-playstation driver.
+///
+/// It carries no serial number, the serial and MAC address are in the pairing info report.
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, PartialEq, Eq, Clone, Debug)]
+#[repr(C)]
+pub struct DeviceInfoReport {
+    report_id: u8,
+    build_date: [u8; 11], // e.g. "Sep 21 2020"
+    build_time: [u8; 8],  // e.g. "04:50:51"
+    firmware_type: U16<LE>,
+    software_series: U16<LE>,
+    hardware_version: U32<LE>,
+    firmware_version: U32<LE>,
+    device_info: [u8; 12],
+    update_version: U16<LE>,
+    reserved: [u8; 14],
+    crc32: U32<LE>,
+}
+const _: () =
+    assert!(core::mem::size_of::<DeviceInfoReport>() == DS_FEATURE_REPORT_FIRMWARE_INFO_SIZE);
+
+impl DeviceInfoReport {
+    pub fn parse(data: &[u8]) -> Option<&Self> {
+        if *data.first()? != DS_FEATURE_REPORT_FIRMWARE_INFO {
+            return None;
+        }
+        let bytes = data.get(..DS_FEATURE_REPORT_FIRMWARE_INFO_SIZE)?;
+        Self::ref_from_bytes(bytes).ok()
+    }
+
+    pub fn hardware_version(&self) -> u32 {
+        self.hardware_version.get()
+    }
+
+    pub fn firmware_version(&self) -> u32 {
+        self.firmware_version.get()
+    }
+
+    /// Version of the firmware update that was installed, used by Sony's updater
+    pub fn update_version(&self) -> u16 {
+        self.update_version.get()
+    }
+
+    pub fn installed_firmware(&self) -> FirmwareVersion {
+        FirmwareVersion(self.update_version())
+    }
+
+    pub fn build_date(&self) -> &str {
+        trim_ascii(&self.build_date)
+    }
+
+    pub fn build_time(&self) -> &str {
+        trim_ascii(&self.build_time)
+    }
+}
+
+/// Firmware update version, the one compared when looking for updates. Shown in hex,
+/// e.g. `0224`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FirmwareVersion(pub u16);
+
+impl core::fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:04x}", self.0)
+    }
+}
+
+/// The hex form shown by `Display`, with an optional `0x` prefix
+impl core::str::FromStr for FirmwareVersion {
+    type Err = core::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        u16::from_str_radix(hex, 16).map(Self)
+    }
+}
+
+/// `Some(latest)` when `current` is older than the latest known firmware
+pub fn check_firmware_update(
+    current: &FirmwareVersion,
+    latest: &FirmwareVersion,
+) -> Option<FirmwareVersion> {
+    (latest > current).then_some(*latest)
+}
+
+/// Pairing info feature report 0x09. hid-playstation only reads the controller address,
+/// the host address follows it like in the DualShock 4 report 0x12.
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, PartialEq, Eq, Clone, Debug)]
+#[repr(C)]
+pub struct PairingInfo {
+    report_id: u8,
+    controller_mac: [u8; 6], // little endian
+    reserved: [u8; 3],
+    host_mac: [u8; 6], // little endian
+    reserved2: [u8; 4],
+}
+const _: () = assert!(core::mem::size_of::<PairingInfo>() == DS_FEATURE_REPORT_PAIRING_INFO_SIZE);
+
+impl PairingInfo {
+    pub fn parse(data: &[u8]) -> Option<&Self> {
+        if *data.first()? != DS_FEATURE_REPORT_PAIRING_INFO {
+            return None;
+        }
+        let bytes = data.get(..DS_FEATURE_REPORT_PAIRING_INFO_SIZE)?;
+        Self::ref_from_bytes(bytes).ok()
+    }
+
+    /// Bluetooth address of the controller, most significant byte first
+    pub fn controller_mac(&self) -> [u8; 6] {
+        let mut mac = self.controller_mac;
+        mac.reverse();
+        mac
+    }
+
+    /// Bluetooth address of the console the controller is paired with, all zeros if none
+    pub fn host_mac(&self) -> [u8; 6] {
+        let mut mac = self.host_mac;
+        mac.reverse();
+        mac
+    }
+
+    /// e.g. "A0:AB:51:12:34:56"
+    pub fn controller_mac_string(&self) -> String {
+        format_mac(self.controller_mac())
+    }
+
+    pub fn host_mac_string(&self) -> String {
+        format_mac(self.host_mac())
+    }
+}
+
+fn format_mac(mac: [u8; 6]) -> String {
+    mac.map(|byte| format!("{byte:02X}")).join(":")
+}
+
+fn trim_ascii(bytes: &[u8]) -> &str {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    core::str::from_utf8(&bytes[..end]).unwrap_or("").trim()
+}
+
+#[derive(Debug, Clone)]
+pub struct DualSenseOutputReportBuilder {
+    report: DualSenseOutputReport,
+}
+
+impl DualSenseOutputReportBuilder {
+    /// Starts from a report that leaves every feature unchanged
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use dualsense_tray::dualsense::proto::DualSenseOutputReportBuilder;
+    /// let report = DualSenseOutputReportBuilder::new()
+    ///     .rumble(128, 64)
+    ///     .lightbar(255, 0, 0)
+    ///     .build();
+    /// assert_eq!(report.rumble(), (128, 64));
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            report: DualSenseOutputReport::default(),
+        }
+    }
+
+    pub fn rumble(mut self, strong: u8, weak: u8) -> Self {
+        self.report
+            .enable(OutputFeatures::COMPATIBLE_VIBRATION | OutputFeatures::HAPTICS_SELECT);
+        self.report.motor_left = strong;
+        self.report.motor_right = weak;
+        self
+    }
+
+    pub fn lightbar(mut self, red: u8, green: u8, blue: u8) -> Self {
+        self.report.enable(OutputFeatures::LIGHTBAR);
+        self.report.lightbar_red = red;
+        self.report.lightbar_green = green;
+        self.report.lightbar_blue = blue;
+        self
+    }
+
+    /// Sets `effect` at full brightness, animations need the frames of
+    /// `LightbarEffect::color_at` written continuously
+    pub fn lightbar_effect(self, effect: LightbarEffect) -> Self {
+        let (red, green, blue) = match effect {
+            LightbarEffect::Pulse { r, g, b, .. } => (r, g, b),
+            _ => effect.color_at(core::time::Duration::ZERO),
+        };
+        self.lightbar(red, green, blue)
+    }
+
+    /// Bitmask of the five player indicator LEDs, left to right
+    pub fn player_leds(mut self, leds: u8) -> Self {
+        self.report.enable(OutputFeatures::PLAYER_INDICATOR_ENABLE);
+        self.report.player_leds = leds & DS_PLAYER_LEDS_MASK;
+        self
+    }
+
+    pub fn left_trigger_effect(mut self, effect: TriggerEffect) -> Self {
+        self.report.enable(OutputFeatures::LEFT_TRIGGER_EFFECT);
+        self.report.left_trigger_effect = effect.to_bytes();
+        self
+    }
+
+    pub fn right_trigger_effect(mut self, effect: TriggerEffect) -> Self {
+        self.report.enable(OutputFeatures::RIGHT_TRIGGER_EFFECT);
+        self.report.right_trigger_effect = effect.to_bytes();
+        self
+    }
+
+    pub fn mic_led(mut self, on: bool) -> Self {
+        self.report.enable(OutputFeatures::MIC_LED);
+        self.report.mute_button_led = on as u8;
+        self
+    }
+
+    pub fn build(self) -> DualSenseOutputReport {
+        self.report
+    }
+}
+
+impl Default for DualSenseOutputReportBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds input reports with given field values, e.g. as test fixtures
+#[derive(Debug, Clone)]
+pub struct DualSenseInputReportBuilder {
+    report: DualSenseInputReport,
+}
+
+impl DualSenseInputReportBuilder {
+    /// Starts from a controller at rest: centered sticks, nothing pressed or touched
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use dualsense_tray::dualsense::proto::{Button, DualSenseInputReportBuilder};
+    /// let report = DualSenseInputReportBuilder::new()
+    ///     .button(Button::Cross, true)
+    ///     .battery(80, false)
+    ///     .build();
+    /// assert!(report.is_pressed(Button::Cross));
+    /// assert_eq!(report.battery(), (80, false));
+    /// ```
+    pub fn new() -> Self {
+        let mut report = DualSenseInputReport::new_zeroed();
+        (report.x, report.y, report.rx, report.ry) = (0x80, 0x80, 0x80, 0x80);
+        report.buttons[0] = DS_DPAD_NEUTRAL;
+        for point in &mut report.points {
+            point.contact = DS_TOUCH_POINT_INACTIVE;
+        }
+        Self { report }
+    }
+
+    pub fn left_stick(mut self, x: u8, y: u8) -> Self {
+        (self.report.x, self.report.y) = (x, y);
+        self
+    }
+
+    pub fn right_stick(mut self, rx: u8, ry: u8) -> Self {
+        (self.report.rx, self.report.ry) = (rx, ry);
+        self
+    }
+
+    pub fn left_trigger(mut self, z: u8) -> Self {
+        self.report.z = z;
+        self
+    }
+
+    pub fn right_trigger(mut self, rz: u8) -> Self {
+        self.report.rz = rz;
+        self
+    }
+
+    pub fn button(mut self, button: Button, pressed: bool) -> Self {
+        let (index, mask) = button.mask();
+        if pressed {
+            self.report.buttons[index] |= mask;
+        } else {
+            self.report.buttons[index] &= !mask;
+        }
+        self
+    }
+
+    pub fn dpad(mut self, dpad: DPad) -> Self {
+        self.report.buttons[0] = (self.report.buttons[0] & !DS_BUTTONS0_DPAD) | dpad.to_hat();
+        self
+    }
+
+    pub fn gyro(mut self, gx: i16, gy: i16, gz: i16) -> Self {
+        self.report.gyro = [gx, gy, gz].map(|v| U16::new(v as u16));
+        self
+    }
+
+    pub fn accel(mut self, ax: i16, ay: i16, az: i16) -> Self {
+        self.report.accel = [ax, ay, az].map(|v| U16::new(v as u16));
+        self
+    }
+
+    /// `capacity` in percent, rounded down to the 10% steps the controller reports
+    pub fn battery(mut self, capacity: u8, charging: bool) -> Self {
+        let capacity = capacity.min(100) / 10;
+        self.report.status = capacity | ((charging as u8) << DS_STATUS_CHARGING_SHIFT);
+        self
+    }
+
+    pub fn seq(mut self, n: u8) -> Self {
+        self.report.seq_number = n;
+        self
+    }
+
+    pub fn touch_point(mut self, slot: usize, point: DualSenseTouchPoint) -> Self {
+        self.report.points[slot] = point;
+        self
+    }
+
+    pub fn build(self) -> DualSenseInputReport {
+        self.report
+    }
+}
+
+impl Default for DualSenseInputReportBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// CRC32 of `seed` followed by `data`, as carried by Bluetooth reports
+pub fn ds_crc32(seed: u8, data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in core::iter::once(seed).chain(data.iter().copied()) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::*;
+
+    fn usb_report([x, y, rx, ry]: [u8; 4]) -> DualSenseInputReport {
+        DualSenseInputReportBuilder::new()
+            .left_stick(x, y)
+            .right_stick(rx, ry)
+            .build()
+    }
+
+    #[test]
+    fn stick_polar_right() {
+        let report = usb_report([0xFF, 0x80, 0x80, 0x80]);
+        let (angle, magnitude) = report.left_stick_polar();
+        assert!(angle.abs() < 1e-6);
+        assert!((magnitude - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stick_polar_up() {
+        let report = usb_report([0x80, 0x80, 0x80, 0x00]);
+        let (angle, magnitude) = report.right_stick_polar();
+        assert!((angle - core::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert!((magnitude - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stick_polar_corner_is_clamped() {
+        let report = usb_report([0xFF, 0x00, 0x00, 0xFF]);
+        let (angle, magnitude) = report.left_stick_polar();
+        assert!((angle - core::f32::consts::FRAC_PI_4).abs() < 1e-6);
+        assert_eq!(magnitude, 1.0);
+        let (_, magnitude) = report.right_stick_polar();
+        assert_eq!(magnitude, 1.0);
+    }
+
+    #[test]
+    fn display_idle_report() {
+        let report = DualSenseInputReportBuilder::new().battery(70, true).build();
+        assert_eq!(
+            report.to_string(),
+            "Sticks: L(0.00, 0.00) R(0.00, 0.00) | Triggers: L=0.00 R=0.00 | Buttons: none \
+             | DPad: Neutral | Battery: 70% charging | Touch: none"
+        );
+    }
+
+    #[test]
+    fn sorted_touch_points_by_id() {
+        let mut report = usb_report([0x80, 0x80, 0x80, 0x80]);
+        report.points[0].contact = 5;
+        report.points[1].contact = 3;
+        let [first, second] = report.sorted_touch_points();
+        assert_eq!((first.id(), second.id()), (3, 5));
+
+        report.points[1].contact = DS_TOUCH_POINT_INACTIVE | 1;
+        let [first, second] = report.sorted_touch_points();
+        assert_eq!(first.id(), 5);
+        assert!(!second.is_active());
+    }
+
+    #[test]
+    fn gyro_delta_integrates_rate() {
+        let mut prev = usb_report([0x80; 4]);
+        // 90 deg/s around every axis
+        let counts = (90.0 * DS_GYRO_COUNTS_PER_DEG_S).round() as u16;
+        prev.gyro = [U16::new(counts); 3];
+        prev.sensor_timestamp = U32::new(u32::MAX - 5_999);
+
+        let mut total = [0.0; 3];
+        for _ in 0..250 {
+            let mut report = prev.clone();
+            let ticks = report.sensor_timestamp.get().wrapping_add(12_000);
+            report.sensor_timestamp = U32::new(ticks);
+            let dt_us = report.timestamp_elapsed_us(&prev);
+            assert_eq!(dt_us, 4_000);
+
+            let delta = report.gyro_delta(&prev, dt_us);
+            for (total, delta) in total.iter_mut().zip(delta) {
+                *total += delta;
+            }
+            prev = report;
+        }
+        for total in total {
+            assert!((total - 90.0).abs() < 0.05, "{total}");
+        }
+    }
+
+    #[test]
+    fn imu_data_calibrated() {
+        let mut report = DualSenseInputReportBuilder::new()
+            .gyro(1000, -1000, 500)
+            .accel(8192, 0, -8192)
+            .build();
+        report.sensor_timestamp = U32::new(3_000_000);
+
+        let nominal = report.imu_data(None);
+        assert_eq!(nominal.accel, [1.0, 0.0, -1.0]);
+        assert_eq!(nominal.timestamp_us, 1_000_000);
+
+        // report 0x05 in the order hid-playstation reads it, with different values per axis:
+        // biases -3, 5, 2, plus/minus per axis 8870/-8874, 8851/-8861, 8930/-8919,
+        // speeds 540/540, accel plus/minus 8233/-8209, 8278/-8110, 8051/-8333
+        let buf: [u8; DS_FEATURE_REPORT_BT_FULL_SIZE] = [
+            0x05, 0xfd, 0xff, 0x05, 0x00, 0x02, 0x00, 0xa6, 0x22, 0x56, 0xdd, 0x93, 0x22, 0x63,
+            0xdd, 0xe2, 0x22, 0x29, 0xdd, 0x1c, 0x02, 0x1c, 0x02, 0x29, 0x20, 0xef, 0xdf, 0x56,
+            0x20, 0x52, 0xe0, 0x73, 0x1f, 0x73, 0xdf, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let calibration = ImuCalibration::parse(&buf).unwrap();
+
+        // (raw - bias) * (speed_plus + speed_minus) / (|plus - bias| + |minus - bias|)
+        let calibrated = report.imu_data(Some(calibration));
+        let gyro = [
+            1003.0 * 1080.0 / 17744.0,
+            -1005.0 * 1080.0 / 17712.0,
+            498.0 * 1080.0 / 17849.0,
+        ];
+        for (calibrated, expected) in calibrated.gyro.iter().zip(gyro) {
+            assert!(
+                (calibrated - expected).abs() < 1e-3,
+                "{calibrated} != {expected}"
+            );
+        }
+        let accel = [0.99501, -0.01025, -0.98279];
+        for (calibrated, expected) in calibrated.accel.iter().zip(accel) {
+            assert!(
+                (calibrated - expected).abs() < 1e-4,
+                "{calibrated} != {expected}"
+            );
+        }
+        assert_eq!(calibrated.timestamp_us, nominal.timestamp_us);
+    }
+
+    #[test]
+    fn touch_point_bytes_round_trip() {
+        let point = DualSenseTouchPoint::new(42, 1919, 1079, true);
+        assert_eq!((point.id(), point.x(), point.y()), (42, 1919, 1079));
+
+        let bytes = point.to_bytes();
+        assert_eq!(bytes, [42, 0x7F, 0x77, 0x43]);
+        assert_eq!(DualSenseTouchPoint::ref_from_bytes(&bytes).unwrap(), &point);
+        assert!(!DualSenseTouchPoint::new(1, 0, 0, false).is_active());
+    }
+
+    #[test]
+    fn touch_point_distance() {
+        let origin = DualSenseTouchPoint::new(1, 0, 0, true);
+        let point = DualSenseTouchPoint::new(2, 3, 4, true);
+        assert_eq!(origin.distance_to(&point), Some(5.0));
+        assert_eq!(origin.midpoint(&point), Some((1.5, 2.0)));
+
+        let lifted = DualSenseTouchPoint::new(3, 3, 4, false);
+        assert_eq!(origin.distance_to(&lifted), None);
+        assert_eq!(lifted.midpoint(&origin), None);
+    }
+
+    #[test]
+    fn touch_point_float_positions() {
+        // x = 960, y = 540
+        let mut point = DualSenseTouchPoint {
+            contact: 1,
+            x_lo: 0xC0,
+            xhi_ylo: 0x3C,
+            y_hi: 0x21,
+        };
+        assert_eq!(point.position_f32(), Some((960.0, 540.0)));
+        assert_eq!(point.position_normalized_f64(), Some((0.5, 0.5)));
+
+        point.contact |= DS_TOUCH_POINT_INACTIVE;
+        assert_eq!(point.position_f32(), None);
+        assert_eq!(point.position_normalized_f64(), None);
+    }
+
+    #[test]
+    fn touchpad_fingers_and_buttons() {
+        let mut report = usb_report([0x80; 4]);
+        report.points[0].contact = 7;
+        report.points[1].contact = DS_TOUCH_POINT_INACTIVE;
+        report.buttons[2] = 0x02;
+        assert!(report.is_touchpad_finger_down(0));
+        assert!(!report.is_touchpad_finger_down(1));
+        assert!(!report.is_touchpad_finger_down(2));
+        assert!(report.touchpad_button_pressed());
+        assert!(!report.is_mute_button_pressed());
+
+        report.buttons[2] = 0x04;
+        assert!(!report.touchpad_button_pressed());
+        assert!(report.is_mute_button_pressed());
+    }
+
+    #[test]
+    fn interpolate_halfway() {
+        let a = DualSenseInputReportBuilder::new()
+            .left_stick(0x00, 0x80)
+            .gyro(-100, 0, 0)
+            .build();
+        let b = DualSenseInputReportBuilder::new()
+            .left_stick(0xFF, 0x80)
+            .gyro(100, 0, 0)
+            .button(Button::Cross, true)
+            .build();
+
+        let mid = interpolate(&a, &b, 0.5);
+        assert_eq!(mid.sticks_raw().0, 0x80);
+        assert_eq!(mid.raw_gyro()[0], 0);
+        assert!(mid.is_pressed(Button::Cross));
+
+        let early = interpolate(&a, &b, 0.25);
+        assert!(!early.is_pressed(Button::Cross));
+        assert_eq!(interpolate(&a, &b, 0.0), a);
+        assert_eq!(interpolate(&a, &b, 1.0), b);
+    }
+
+    #[test]
+    fn buttons_and_dpad_changes() {
+        let mut prev = usb_report([0x80; 4]);
+        prev.buttons = [DS_DPAD_NEUTRAL | 0x10, 0x01, 0, 0]; // Square, L1
+        let mut report = prev.clone();
+        assert_eq!(report.buttons_changed(&prev), (vec![], vec![]));
+        assert_eq!(report.dpad_changed(&prev), None);
+
+        report.buttons = [0x02 | 0x20, 0x01, 0x01, 0]; // Cross, L1, PS, d-pad right
+        assert_eq!(
+            report.buttons_changed(&prev),
+            (vec![Button::Cross, Button::PS], vec![Button::Square])
+        );
+        assert_eq!(report.dpad_changed(&prev), Some(DPad::Right));
+    }
+
+    #[test]
+    fn feature_vec_layout() {
+        let mut report = usb_report([0xFF, 0x80, 0x80, 0x80]);
+        report.rz = 0xFF;
+        report.buttons = [DS_DPAD_NEUTRAL | 0x20, 0, 0x04, 0]; // Cross, Mute
+        let features = report.to_feature_vec();
+        assert_eq!(features[FEAT_LEFT_STICK_X], 1.0);
+        assert_eq!(features[FEAT_RIGHT_TRIGGER], 1.0);
+        assert_eq!(features[FEAT_BUTTONS + 1], 1.0);
+        assert_eq!(features[FEAT_BUTTONS + Button::ALL.len() - 1], 1.0);
+        assert_eq!(features.iter().filter(|&&f| f != 0.0).count(), 4);
+    }
+
+    #[test]
+    fn device_info_report() {
+        let mut buf = [0u8; DS_FEATURE_REPORT_FIRMWARE_INFO_SIZE];
+        buf[0] = DS_FEATURE_REPORT_FIRMWARE_INFO;
+        buf[1..12].copy_from_slice(b"Sep 21 2020");
+        buf[12..20].copy_from_slice(b"04:50:51");
+        buf[24..28].copy_from_slice(&0x0000_0613u32.to_le_bytes());
+        buf[28..32].copy_from_slice(&0x0110_002Au32.to_le_bytes());
+        buf[44..46].copy_from_slice(&0x0224u16.to_le_bytes());
+
+        let info = DeviceInfoReport::parse(&buf).unwrap();
+        assert_eq!(info.build_date(), "Sep 21 2020");
+        assert_eq!(info.build_time(), "04:50:51");
+        assert_eq!(info.hardware_version(), 0x0613);
+        assert_eq!(info.firmware_version(), 0x0110_002A);
+        assert_eq!(info.update_version(), 0x0224);
+        assert_eq!(info.installed_firmware().to_string(), "0224");
+
+        assert!(DeviceInfoReport::parse(&buf[..63]).is_none());
+        buf[0] = DS_FEATURE_REPORT_BT_FULL;
+        assert!(DeviceInfoReport::parse(&buf).is_none());
+    }
+
+    #[test]
+    fn firmware_update_only_when_newer() {
+        let current = FirmwareVersion(0x0224);
+        assert_eq!(
+            check_firmware_update(&current, &FirmwareVersion(0x0230)),
+            Some(FirmwareVersion(0x0230))
+        );
+        assert_eq!(check_firmware_update(&current, &current), None);
+        assert_eq!(
+            check_firmware_update(&current, &FirmwareVersion(0x0110)),
+            None
+        );
+    }
+
+    #[test]
+    fn firmware_version_from_hex() {
+        assert_eq!("0224".parse(), Ok(FirmwareVersion(0x0224)));
+        assert_eq!("0x0230".parse(), Ok(FirmwareVersion(0x0230)));
+        assert_eq!(
+            FirmwareVersion(0x0110).to_string().parse(),
+            Ok(FirmwareVersion(0x0110))
+        );
+        assert!("v2".parse::<FirmwareVersion>().is_err());
+    }
+
+    #[test]
+    fn input_report_builder_fields() {
+        let report = DualSenseInputReportBuilder::new()
+            .left_stick(0xFF, 0x80)
+            .right_trigger(0xFF)
+            .button(Button::Triangle, true)
+            .button(Button::R1, true)
+            .button(Button::R1, false)
+            .dpad(DPad::DownLeft)
+            .gyro(-1, 0, 1)
+            .battery(100, true)
+            .seq(7)
+            .build();
+
+        assert_eq!(report.left_stick(), (1.0, 0.0));
+        assert_eq!(report.right_stick(), (0.0, 0.0));
+        assert_eq!(report.right_trigger(), 1.0);
+        assert_eq!(report.pressed_buttons(), vec![Button::Triangle]);
+        assert_eq!(report.dpad(), DPad::DownLeft);
+        assert_eq!(report.raw_gyro(), [0xFFFF, 0, 1]);
+        assert_eq!(report.battery(), (100, true));
+        assert_eq!(report.seq_number(), 7);
+        assert!(!report.is_touchpad_finger_down(0));
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let mut report = usb_report([0x12, 0x34, 0x56, 0x78]);
+        report.buttons = [DS_DPAD_NEUTRAL | 0x20, 0x01, 0x02, 0];
+        report.gyro[1] = U16::new(0xBEEF);
+        report.status = 0x25;
+
+        let usb = encode_usb(&report);
+        assert_eq!(DualSenseInputReport::parse(&usb), Some(&report));
+
+        let bt = encode_bt(&report);
+        assert_eq!(DualSenseInputReport::parse(&bt), Some(&report));
+        let crc = ds_crc32(DS_INPUT_CRC32_SEED, &bt[..74]);
+        assert_eq!(&bt[74..], &crc.to_le_bytes());
+    }
+
+    #[test]
+    fn to_hid_bytes_round_trip() {
+        // every byte value at every offset of the report covers all of its fields
+        for offset in 0..DS_INPUT_REPORT_SIZE {
+            for value in [0x00, 0x01, 0x80, 0xA5, 0xFF] {
+                let mut report = DualSenseInputReportBuilder::new().build();
+                report.as_mut_bytes()[offset] = value;
+
+                let usb = report.to_usb_bytes();
+                assert_eq!(DualSenseInputReport::parse(&usb), Some(&report));
+                let bt = report.to_bt_bytes();
+                assert_eq!(DualSenseInputReport::parse(&bt), Some(&report));
+            }
+        }
+    }
+
+    #[test]
+    fn dualsense_product_ids() {
+        assert!(is_dualsense(SONY_VID, DUALSENSE_PID));
+        assert!(is_dualsense(SONY_VID, DUALSENSE_EDGE_PID));
+        assert!(is_dualsense(SONY_VID, DUALSENSE_ACCESS_PID));
+        assert!(!is_dualsense(SONY_VID, 0x09CC)); // DualShock 4
+        assert!(!is_dualsense(0x045E, DUALSENSE_PID));
+        assert_eq!(
+            DualSenseModel::from_product_id(DUALSENSE_EDGE_PID),
+            Some(DualSenseModel::Edge)
+        );
+    }
+
+    #[test]
+    fn pairing_info_report() {
+        let mut buf = [0u8; DS_FEATURE_REPORT_PAIRING_INFO_SIZE];
+        buf[0] = DS_FEATURE_REPORT_PAIRING_INFO;
+        buf[1..7].copy_from_slice(&[0x56, 0x34, 0x12, 0x51, 0xAB, 0xA0]);
+        buf[10..16].copy_from_slice(&[0x06, 0x05, 0x04, 0x03, 0x02, 0x01]);
+
+        let info = PairingInfo::parse(&buf).unwrap();
+        assert_eq!(info.controller_mac(), [0xA0, 0xAB, 0x51, 0x12, 0x34, 0x56]);
+        assert_eq!(info.controller_mac_string(), "A0:AB:51:12:34:56");
+        assert_eq!(info.host_mac_string(), "01:02:03:04:05:06");
+        assert!(PairingInfo::parse(&buf[..19]).is_none());
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(ds_crc32(b'1', b"23456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn output_report_layout() {
+        let report = DualSenseOutputReportBuilder::new()
+            .rumble(0xAA, 0x55)
+            .lightbar(1, 2, 3)
+            .build();
+
+        let usb = report.to_usb_bytes();
+        assert_eq!(usb[0], DS_OUTPUT_REPORT_USB);
+        assert_eq!(&usb[1..5], &[0x03, 0x04, 0x55, 0xAA]);
+        assert_eq!(&usb[45..48], &[1, 2, 3]);
+
+        let bt = report.to_bt_bytes(0x13);
+        assert_eq!(&bt[..3], &[DS_OUTPUT_REPORT_BT, 0x30, DS_OUTPUT_TAG]);
+        assert_eq!(&bt[3..50], report.as_bytes());
+        let crc = ds_crc32(DS_OUTPUT_CRC32_SEED, &bt[..74]);
+        assert_eq!(&bt[74..], &crc.to_le_bytes());
+    }
+
+    #[test]
+    fn reset_all_features_turns_everything_off() {
+        let report = DualSenseOutputReport::reset_all_features();
+        assert_eq!(report.rumble(), (0, 0));
+        assert_eq!(report.lightbar(), (0, 0, 0));
+        assert_eq!(report.player_leds(), 0);
+        assert_eq!(&report.as_bytes()[..2], &[0x0F, 0x15]);
+        assert!(report.as_bytes()[2..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn output_features_map_to_valid_flags() {
+        let report = DualSenseOutputReportBuilder::new()
+            .rumble(1, 1)
+            .player_leds(0x04)
+            .build();
+        assert_eq!(
+            report.features(),
+            OutputFeatures::COMPATIBLE_VIBRATION
+                | OutputFeatures::HAPTICS_SELECT
+                | OutputFeatures::PLAYER_INDICATOR_ENABLE
+        );
+        assert_eq!(&report.as_bytes()[..2], &[0x03, 0x10]);
+
+        let mut report = DualSenseOutputReport::default();
+        report.enable(OutputFeatures::LIGHTBAR_SETUP);
+        assert_eq!(report.valid_flag2, 0x02);
+    }
+
+    #[test]
+    fn trigger_effect_layout() {
+        let effect = TriggerEffect::PositionFeedback {
+            start_position: 0x20,
+            end_position: 0xC0,
+            strength: 0x80,
+        };
+        let report = DualSenseOutputReportBuilder::new()
+            .left_trigger_effect(effect)
+            .right_trigger_effect(TriggerEffect::Off)
+            .build();
+
+        let usb = report.to_usb_bytes();
+        assert_eq!(usb[1], 0x0C);
+        // right trigger block starts at byte 11, left at 22
+        assert_eq!(&usb[11..22], &[0; 11]);
+        assert_eq!(&usb[22..26], &[DS_TRIGGER_EFFECT_SECTION, 0x20, 0xC0, 0x80]);
+    }
+
+    // `read_input_report` reads both report types into one buffer and transmutes it, which
+    // relies on the USB report being padded to the Bluetooth size
+    #[test]
+    fn input_report_wrappers_share_buffer_size() {
+        assert_eq!(
+            core::mem::size_of::<DualSenseInputReportUSB>(),
+            core::mem::size_of::<DualSenseInputReportBT>()
+        );
+        assert_eq!(
+            core::mem::size_of::<DualSenseInputReportUSB>(),
+            DS_INPUT_REPORT_BT_SIZE
+        );
+        assert_eq!(
+            core::mem::size_of::<DualSenseInputReportBT>(),
+            DS_INPUT_REPORT_BT_SIZE
+        );
+    }
+
+    #[test]
+    fn trigger_state_in_effect_zone() {
+        let effect = TriggerEffect::PositionFeedback {
+            start_position: 0x40,
+            end_position: 0x80,
+            strength: 0xFF,
+        };
+        let report = DualSenseInputReportBuilder::new()
+            .left_trigger(0x60)
+            .right_trigger(0xFF)
+            .build();
+
+        let left = report.left_trigger_state(Some(&effect));
+        assert_eq!(left.position, 0x60 as f32 / 255.0);
+        assert_eq!(left.in_effect_zone, Some(true));
+        assert_eq!(
+            report.right_trigger_state(Some(&effect)).in_effect_zone,
+            Some(false)
+        );
+        assert_eq!(
+            report
+                .left_trigger_state(Some(&TriggerEffect::Off))
+                .in_effect_zone,
+            Some(false)
+        );
+        assert_eq!(report.left_trigger_state(None).in_effect_zone, None);
+    }
+
+    #[test]
+    fn lightbar_pulse_frames() {
+        let pulse = LightbarEffect::Pulse {
+            r: 200,
+            g: 100,
+            b: 0,
+            rate: 30,
+        };
+        assert_eq!(pulse.color_at(Duration::ZERO), (0, 0, 0));
+        assert_eq!(pulse.color_at(Duration::from_secs(1)), (200, 100, 0));
+        assert_eq!(pulse.color_at(Duration::from_millis(500)), (100, 50, 0));
+        assert_eq!(pulse.color_at(Duration::from_secs(2)), (0, 0, 0));
+
+        let report = DualSenseOutputReportBuilder::new()
+            .lightbar_effect(LightbarEffect::Static { r: 1, g: 2, b: 3 })
+            .build();
+        assert_eq!(report.lightbar(), (1, 2, 3));
+        // a pulse isn't written as its dark first frame
+        let report = DualSenseOutputReportBuilder::new()
+            .lightbar_effect(pulse)
+            .build();
+        assert_eq!(report.lightbar(), (200, 100, 0));
+    }
+
+    #[test]
+    fn charging_status_nibble() {
+        let mut report = DualSenseInputReportBuilder::new()
+            .battery(100, false)
+            .build();
+        assert_eq!(report.charging_status(), ChargingStatus::Discharging);
+        assert_eq!(report.charging_source(), None);
+
+        report.status = (DS_CHARGING_STATUS_FULL << DS_STATUS_CHARGING_SHIFT) | 10;
+        assert_eq!(report.charging_status(), ChargingStatus::Full);
+        assert_eq!(report.battery(), (100, false));
+        assert!(report.is_connected_to_power());
+        assert_eq!(report.charging_source(), Some(ChargingSource::USB));
+
+        report.status = DS_CHARGING_STATUS_TEMPERATURE_ERROR << DS_STATUS_CHARGING_SHIFT;
+        assert_eq!(report.charging_status(), ChargingStatus::TemperatureError);
+        assert!(report.is_connected_to_power());
+    }
+
+    #[test]
+    fn battery_level_preset() {
+        let preset = LightbarPreset::BatteryLevel;
+        assert_eq!(from_preset(preset, Some(100)), (0, 255, 0));
+        assert_eq!(from_preset(preset, Some(40)), (255, 255, 0));
+        assert_eq!(from_preset(preset, Some(10)), (255, 0, 0));
+        assert_eq!(from_preset(preset, None), (255, 255, 0));
+        assert_eq!(from_preset(LightbarPreset::BluePS, Some(10)), (0, 0, 128));
+    }
+
+    #[test]
+    fn deadzone_shapes_on_diagonal() {
+        let diagonal = (0.5, 0.5);
+        let approx = |(x, y): (f32, f32), (ex, ey): (f32, f32)| {
+            assert!(
+                (x - ex).abs() < 1e-4 && (y - ey).abs() < 1e-4,
+                "({x}, {y}) != ({ex}, {ey})"
+            );
+        };
+
+        let circular = DeadzoneShape::Circular.apply(diagonal, 0.3);
+        let per_axis = (0.5 * core::f32::consts::SQRT_2 - 0.3) / 0.7 / core::f32::consts::SQRT_2;
+        approx(circular, (per_axis, per_axis));
+
+        let square = DeadzoneShape::Square.apply(diagonal, 0.3);
+        approx(square, (0.2 / 0.7, 0.2 / 0.7));
+
+        let cross = DeadzoneShape::Cross.apply(diagonal, 0.3);
+        approx(cross, diagonal);
+
+        assert_ne!(circular, square);
+        assert_ne!(square, cross);
+        assert_ne!(cross, circular);
+    }
+
+    #[test]
+    fn deadzone_cross_zeroes_minor_axis() {
+        assert_eq!(DeadzoneShape::Cross.apply((0.8, 0.2), 0.3), (0.8, 0.0));
+        assert_eq!(DeadzoneShape::Cross.apply((0.1, -0.9), 0.3), (0.0, -0.9));
+        assert_eq!(DeadzoneShape::Circular.apply((0.2, 0.2), 0.3), (0.0, 0.0));
+    }
+
+    #[test]
+    fn deadzone_edge_thresholds() {
+        for shape in [
+            DeadzoneShape::Circular,
+            DeadzoneShape::Square,
+            DeadzoneShape::Cross,
+        ] {
+            assert_eq!(shape.apply((0.0, 0.0), 0.0), (0.0, 0.0), "{shape:?}");
+            assert_eq!(shape.apply((0.0, 0.0), 1.0), (0.0, 0.0), "{shape:?}");
+        }
+        // a deadzone over the whole range only lets full deflection through
+        for shape in [DeadzoneShape::Circular, DeadzoneShape::Square] {
+            assert_eq!(shape.apply((1.0, 0.0), 1.0), (1.0, 0.0), "{shape:?}");
+            assert_eq!(shape.apply((0.5, 0.0), 1.0), (0.0, 0.0), "{shape:?}");
+        }
+        assert_eq!(DeadzoneShape::Square.apply((0.5, -0.5), -1.0), (0.5, -0.5));
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use super::{async_hid::DualSenseConnection, error::DualSenseResult, proto::DualSenseOutputReport};
+
+pub type SinkFuture<'a> = Pin<Box<dyn Future<Output = DualSenseResult<()>> + Send + 'a>>;
+
+/// Destination of output reports, so output code can be tested without hardware
+pub trait DualSenseSink: Send + Sync {
+    fn write_output_report(&self, report: DualSenseOutputReport) -> SinkFuture<'_>;
+}
+
+impl DualSenseSink for DualSenseConnection {
+    fn write_output_report(&self, report: DualSenseOutputReport) -> SinkFuture<'_> {
+        Box::pin(DualSenseConnection::write_output_report(self, report))
+    }
+}
+
+/// Shares a sink, e.g. to look at a `RecordingSink` after handing it to a `DeviceManager`
+impl<S: DualSenseSink + ?Sized> DualSenseSink for Arc<S> {
+    fn write_output_report(&self, report: DualSenseOutputReport) -> SinkFuture<'_> {
+        (**self).write_output_report(report)
+    }
+}
+
+/// Discards every report
+#[derive(Debug, Default)]
+pub struct NullSink;
+
+impl DualSenseSink for NullSink {
+    fn write_output_report(&self, _report: DualSenseOutputReport) -> SinkFuture<'_> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Keeps every report it receives, in order
+#[derive(Debug, Default)]
+pub struct RecordingSink {
+    reports: Mutex<Vec<DualSenseOutputReport>>,
+}
+
+impl RecordingSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reports(&self) -> Vec<DualSenseOutputReport> {
+        self.reports.lock().unwrap().clone()
+    }
+}
+
+impl DualSenseSink for RecordingSink {
+    fn write_output_report(&self, report: DualSenseOutputReport) -> SinkFuture<'_> {
+        self.reports.lock().unwrap().push(report);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dualsense::proto::DualSenseOutputReportBuilder;
+
+    #[test]
+    fn recording_sink_keeps_reports_in_order() {
+        let sink = RecordingSink::new();
+        let first = DualSenseOutputReportBuilder::new()
+            .lightbar(255, 0, 0)
+            .build();
+        let second = DualSenseOutputReportBuilder::new().rumble(128, 0).build();
+
+        smol::block_on(async {
+            let sink: &dyn DualSenseSink = &sink;
+            sink.write_output_report(first.clone()).await.unwrap();
+            sink.write_output_report(second.clone()).await.unwrap();
+        });
+        assert_eq!(sink.reports(), [first, second]);
+    }
+}
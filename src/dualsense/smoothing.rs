@@ -0,0 +1,169 @@
+/// Exponential moving average over stick positions, to remove jitter in UI applications.
+///
+/// `alpha` in `0.0..=1.0` is the weight of the newest position: 1.0 disables smoothing,
+/// 0.1 smooths heavily at the cost of latency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmoothingFilter {
+    alpha: f32,
+    prev: Option<(f32, f32)>,
+}
+
+impl SmoothingFilter {
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.clamp(0.0, 1.0),
+            prev: None,
+        }
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    /// The first value passes through unchanged, there is no history to blend it with yet.
+    pub fn apply(&mut self, (x, y): (f32, f32)) -> (f32, f32) {
+        let smoothed = match self.prev {
+            Some((px, py)) => (
+                self.alpha * x + (1.0 - self.alpha) * px,
+                self.alpha * y + (1.0 - self.alpha) * py,
+            ),
+            None => (x, y),
+        };
+        self.prev = Some(smoothed);
+        smoothed
+    }
+
+    pub fn reset(&mut self) {
+        self.prev = None;
+    }
+}
+
+pub const DEFAULT_KALMAN_Q: f32 = 0.001;
+pub const DEFAULT_KALMAN_R: f32 = 0.1;
+
+/// Constant velocity Kalman filter over stick positions, for each axis independently.
+///
+/// `q` is the process noise, how quickly the stick is expected to change speed, and `r` the
+/// measurement noise. A higher `r / q` smooths more; the defaults flatten the 1-2 count
+/// jitter at rest while still following deliberate movements.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StickKalmanFilter {
+    q: f32,
+    r: f32,
+    axes: [KalmanAxis; 2],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct KalmanAxis {
+    position: f32,
+    velocity: f32,
+    /// Error covariance of (position, velocity)
+    p: [[f32; 2]; 2],
+}
+
+impl KalmanAxis {
+    fn new() -> Self {
+        Self {
+            position: 0.0,
+            velocity: 0.0,
+            // nothing is known before the first measurement
+            p: [[1.0, 0.0], [0.0, 1.0]],
+        }
+    }
+
+    /// One report apart, so the time step is 1
+    fn update(&mut self, measurement: f32, q: f32, r: f32) -> f32 {
+        let [[p00, p01], [p10, p11]] = self.p;
+        self.position += self.velocity;
+        let p00 = p00 + p01 + p10 + p11 + q / 4.0;
+        let p01 = p01 + p11 + q / 2.0;
+        let p10 = p10 + p11 + q / 2.0;
+        let p11 = p11 + q;
+
+        let s = p00 + r;
+        let (k0, k1) = (p00 / s, p10 / s);
+        let innovation = measurement - self.position;
+        self.position += k0 * innovation;
+        self.velocity += k1 * innovation;
+        self.p = [
+            [(1.0 - k0) * p00, (1.0 - k0) * p01],
+            [p10 - k1 * p00, p11 - k1 * p01],
+        ];
+        self.position
+    }
+}
+
+impl StickKalmanFilter {
+    pub fn new(q: f32, r: f32) -> Self {
+        Self {
+            q,
+            r,
+            axes: [KalmanAxis::new(), KalmanAxis::new()],
+        }
+    }
+
+    pub fn update(&mut self, (x, y): (f32, f32)) -> (f32, f32) {
+        let [axis_x, axis_y] = &mut self.axes;
+        (
+            axis_x.update(x, self.q, self.r),
+            axis_y.update(y, self.q, self.r),
+        )
+    }
+
+    pub fn reset(&mut self) {
+        self.axes = [KalmanAxis::new(), KalmanAxis::new()];
+    }
+}
+
+impl Default for StickKalmanFilter {
+    fn default() -> Self {
+        Self::new(DEFAULT_KALMAN_Q, DEFAULT_KALMAN_R)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kalman_settles_within_noise() {
+        // two counts of deterministic jitter around a resting position
+        let noise = 2.0 / 127.0;
+        let mut seed = 1u32;
+        let mut jitter = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            ((seed >> 16) as f32 / 65_535.0 * 2.0 - 1.0) * noise
+        };
+
+        let mut filter = StickKalmanFilter::default();
+        for _ in 0..200 {
+            filter.update((0.5 + jitter(), -0.25 + jitter()));
+        }
+        for _ in 0..100 {
+            let (x, y) = filter.update((0.5 + jitter(), -0.25 + jitter()));
+            assert!((x - 0.5).abs() < noise, "{x}");
+            assert!((y + 0.25).abs() < noise, "{y}");
+        }
+    }
+
+    /// Number of samples of a 0 to 1 step until the output is within 1% of the target
+    fn step_iterations(alpha: f32) -> usize {
+        let mut filter = SmoothingFilter::new(alpha);
+        filter.apply((0.0, 0.0));
+        (1..)
+            .find(|_| {
+                let (x, y) = filter.apply((1.0, -1.0));
+                assert_eq!(x, -y);
+                1.0 - x < 0.01
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn step_input_converges() {
+        // the remaining error after n samples is (1 - alpha)^n
+        assert_eq!(step_iterations(1.0), 1);
+        assert_eq!(step_iterations(0.5), 7);
+        assert_eq!(step_iterations(0.1), 44);
+    }
+}
@@ -0,0 +1,124 @@
+use std::time::Instant;
+
+use super::{
+    calibration::StickCalibrator, config::DualSenseConfig, history::InputReportHistory,
+    proto::DualSenseInputReport, stats::DualSenseConnectionStats,
+};
+
+/// Reports kept in the history of a `DualSenseState`
+pub const DEFAULT_STATE_HISTORY: usize = 32;
+
+/// Per-controller state machines fed from a single stream of reports, so applications
+/// don't have to thread each of them separately.
+#[derive(Debug, Clone)]
+pub struct DualSenseState<const N: usize = DEFAULT_STATE_HISTORY> {
+    config: DualSenseConfig,
+    calibrator: StickCalibrator,
+    history: InputReportHistory<N>,
+    stats: DualSenseConnectionStats,
+}
+
+impl<const N: usize> DualSenseState<N> {
+    pub fn new(config: DualSenseConfig) -> Self {
+        Self {
+            config,
+            calibrator: StickCalibrator::new(),
+            history: InputReportHistory::new(),
+            stats: DualSenseConnectionStats::new(),
+        }
+    }
+
+    /// Counts the report, feeds it to the calibrator, then stores it
+    pub fn update(&mut self, report: DualSenseInputReport) {
+        self.stats.record_report(Instant::now());
+        self.calibrator.update(&report);
+        self.history.push(report);
+    }
+
+    /// Left stick of the latest report, calibrated and with the configured deadzone applied
+    pub fn left_stick(&self) -> (f32, f32) {
+        let Some(report) = self.history.latest() else {
+            return (0.0, 0.0);
+        };
+        let stick = report.left_stick_calibrated(&self.calibrator);
+        self.config
+            .deadzone_shape
+            .apply(stick, self.config.deadzone)
+    }
+
+    /// Right stick of the latest report, calibrated and with the configured deadzone applied
+    pub fn right_stick(&self) -> (f32, f32) {
+        let Some(report) = self.history.latest() else {
+            return (0.0, 0.0);
+        };
+        let stick = report.right_stick_calibrated(&self.calibrator);
+        self.config
+            .deadzone_shape
+            .apply(stick, self.config.deadzone)
+    }
+
+    pub fn latest(&self) -> Option<&DualSenseInputReport> {
+        self.history.latest()
+    }
+
+    pub fn config(&self) -> &DualSenseConfig {
+        &self.config
+    }
+
+    pub fn calibrator(&self) -> &StickCalibrator {
+        &self.calibrator
+    }
+
+    pub fn calibrator_mut(&mut self) -> &mut StickCalibrator {
+        &mut self.calibrator
+    }
+
+    pub fn history(&self) -> &InputReportHistory<N> {
+        &self.history
+    }
+
+    pub fn stats(&self) -> &DualSenseConnectionStats {
+        &self.stats
+    }
+}
+
+impl<const N: usize> Default for DualSenseState<N> {
+    fn default() -> Self {
+        Self::new(DualSenseConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dualsense::{
+        calibration::DEFAULT_CALIBRATION_SAMPLES, proto::DualSenseInputReportBuilder,
+    };
+
+    fn report(x: u8) -> DualSenseInputReport {
+        DualSenseInputReportBuilder::new()
+            .left_stick(x, 0x80)
+            .build()
+    }
+
+    #[test]
+    fn drift_is_calibrated_away() {
+        let mut state = DualSenseState::<4>::default();
+        assert_eq!(state.left_stick(), (0.0, 0.0));
+
+        // a stick resting slightly off-center, outside the default deadzone
+        for _ in 0..DEFAULT_CALIBRATION_SAMPLES {
+            state.update(report(0x98));
+        }
+        assert!(state.calibrator().is_calibrated());
+        assert_eq!(state.left_stick(), (0.0, 0.0));
+        assert_eq!(state.history().len(), 4);
+        assert_eq!(
+            state.stats().reports_read(),
+            DEFAULT_CALIBRATION_SAMPLES as u64
+        );
+
+        state.update(report(0xFF));
+        assert!(state.left_stick().0 > 0.5);
+    }
+}
@@ -0,0 +1,181 @@
+use std::time::{Duration, Instant};
+
+/// Number of inter-report intervals kept for the instantaneous rate
+const RATE_WINDOW: usize = 10;
+/// Interval between reports at 250Hz, anything longer counts as lag
+const EXPECTED_INTERVAL: Duration = Duration::from_millis(4);
+const LAG_BUCKETS: usize = 16;
+/// Width of a lag histogram bucket, the last bucket also counts every longer lag
+const LAG_BUCKET_MS: u32 = 2;
+
+/// Report counters of a connection, fed with the time each report was read.
+///
+/// USB and Bluetooth full mode report at 250Hz, Bluetooth falls back to a lower rate when
+/// the full mode could not be enabled.
+#[derive(Debug, Clone, Default)]
+pub struct DualSenseConnectionStats {
+    reports_read: u64,
+    first_report_time: Option<Instant>,
+    last_report_time: Option<Instant>,
+    intervals: [Duration; RATE_WINDOW],
+    next_interval: usize,
+    /// Bucket `i` counts reports that arrived `i * 2ms` or more after the expected interval
+    lag_histogram: [u32; LAG_BUCKETS],
+}
+
+impl DualSenseConnectionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_report(&mut self, now: Instant) {
+        if let Some(last) = self.last_report_time {
+            let interval = now.saturating_duration_since(last);
+            self.intervals[self.next_interval] = interval;
+            self.next_interval = (self.next_interval + 1) % RATE_WINDOW;
+
+            let lag = interval.saturating_sub(EXPECTED_INTERVAL);
+            let bucket = lag.as_micros() / (LAG_BUCKET_MS as u128 * 1000);
+            let count = &mut self.lag_histogram[(bucket as usize).min(LAG_BUCKETS - 1)];
+            *count = count.saturating_add(1);
+        }
+        self.first_report_time.get_or_insert(now);
+        self.last_report_time = Some(now);
+        self.reports_read += 1;
+    }
+
+    pub fn reports_read(&self) -> u64 {
+        self.reports_read
+    }
+
+    pub fn first_report_time(&self) -> Option<Instant> {
+        self.first_report_time
+    }
+
+    pub fn last_report_time(&self) -> Option<Instant> {
+        self.last_report_time
+    }
+
+    /// Average rate between the first and the last report, 0 until two reports were read
+    pub fn report_rate_hz(&self) -> f32 {
+        let (Some(first), Some(last)) = (self.first_report_time, self.last_report_time) else {
+            return 0.0;
+        };
+        let elapsed = last.duration_since(first).as_secs_f32();
+        if elapsed == 0.0 {
+            return 0.0;
+        }
+        (self.reports_read - 1) as f32 / elapsed
+    }
+
+    /// Rate over the last intervals between reports
+    pub fn instantaneous_rate_hz(&self) -> f32 {
+        let count = (self.reports_read.saturating_sub(1) as usize).min(RATE_WINDOW);
+        // intervals are written in order, the oldest ones are overwritten first
+        let total: Duration = self.intervals.iter().take(count).sum();
+        if total.is_zero() {
+            return 0.0;
+        }
+        count as f32 / total.as_secs_f32()
+    }
+
+    pub fn lag_histogram(&self) -> &[u32; LAG_BUCKETS] {
+        &self.lag_histogram
+    }
+
+    /// Median lag beyond the expected interval, with the 2ms resolution of the histogram
+    pub fn p50_ms(&self) -> f32 {
+        self.lag_percentile_ms(50)
+    }
+
+    pub fn p95_ms(&self) -> f32 {
+        self.lag_percentile_ms(95)
+    }
+
+    pub fn p99_ms(&self) -> f32 {
+        self.lag_percentile_ms(99)
+    }
+
+    /// Lower bound of the bucket holding the `percent`th percentile, 0 without intervals
+    fn lag_percentile_ms(&self, percent: u32) -> f32 {
+        // the histogram is never reset, u32 products overflow after ~48 hours at 250Hz
+        let total: u64 = self.lag_histogram.iter().map(|count| *count as u64).sum();
+        let target = (total * percent as u64).div_ceil(100);
+        let mut seen = 0;
+        for (bucket, count) in self.lag_histogram.iter().enumerate() {
+            seen += *count as u64;
+            if seen >= target {
+                return (bucket as u32 * LAG_BUCKET_MS) as f32;
+            }
+        }
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_at_250hz() {
+        let start = Instant::now();
+        let mut stats = DualSenseConnectionStats::new();
+        assert_eq!(stats.report_rate_hz(), 0.0);
+
+        for i in 0..=250 {
+            stats.record_report(start + Duration::from_millis(4 * i));
+        }
+        assert_eq!(stats.reports_read(), 251);
+        assert!((stats.report_rate_hz() - 250.0).abs() < 0.1);
+        assert!((stats.instantaneous_rate_hz() - 250.0).abs() < 0.1);
+
+        // a stall only shows up in the instantaneous rate once it is in the window
+        let last = start + Duration::from_millis(1000);
+        for i in 1..=5 {
+            stats.record_report(last + Duration::from_millis(8 * i));
+        }
+        assert!(stats.instantaneous_rate_hz() < 200.0);
+        assert!(stats.report_rate_hz() > 240.0);
+    }
+
+    #[test]
+    fn lag_percentiles() {
+        let mut stats = DualSenseConnectionStats::new();
+        assert_eq!(stats.p95_ms(), 0.0);
+
+        let mut now = Instant::now();
+        stats.record_report(now);
+        // 50 on time, 45 late by 4ms, 4 late by 10ms and one stall
+        let intervals = [(50, 4), (45, 8), (4, 14), (1, 60)];
+        for (count, interval_ms) in intervals {
+            for _ in 0..count {
+                now += Duration::from_millis(interval_ms);
+                stats.record_report(now);
+            }
+        }
+
+        assert_eq!(stats.lag_histogram()[0], 50);
+        assert_eq!(stats.lag_histogram()[2], 45);
+        assert_eq!(stats.lag_histogram()[5], 4);
+        assert_eq!(stats.lag_histogram()[15], 1);
+        assert_eq!(stats.p50_ms(), 0.0);
+        assert_eq!(stats.p95_ms(), 4.0);
+        assert_eq!(stats.p99_ms(), 10.0);
+    }
+
+    #[test]
+    fn lag_percentiles_after_days_connected() {
+        let mut stats = DualSenseConnectionStats::new();
+        // well past u32::MAX / 99 intervals, as after ~48 hours at 250Hz
+        stats.lag_histogram[0] = 50_000_000;
+        stats.lag_histogram[2] = 50_000_000;
+        assert_eq!(stats.p50_ms(), 0.0);
+        assert_eq!(stats.p99_ms(), 4.0);
+
+        stats.lag_histogram[2] = u32::MAX;
+        let now = Instant::now();
+        stats.record_report(now);
+        stats.record_report(now + Duration::from_millis(8));
+        assert_eq!(stats.lag_histogram()[2], u32::MAX);
+    }
+}
@@ -0,0 +1,162 @@
+use std::time::{Duration, Instant};
+
+use smol::Task;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    INPUT, INPUT_0, INPUT_MOUSE, MOUSE_EVENT_FLAGS, MOUSEEVENTF_ABSOLUTE, MOUSEEVENTF_MOVE,
+    MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP, MOUSEINPUT, SendInput,
+};
+
+use super::{
+    async_hid::DualSenseConnection,
+    proto::{DS_TOUCHPAD_HEIGHT, DS_TOUCHPAD_WIDTH, DualSenseInputReport},
+};
+
+/// Longest a two finger touch may last to count as a tap
+pub const TWO_FINGER_TAP_MAX: Duration = Duration::from_millis(250);
+
+/// `SendInput` maps absolute coordinates onto the primary monitor as `0..=65535`
+const ABSOLUTE_MAX: f64 = 65535.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MouseAction {
+    /// Absolute position in `SendInput` units
+    MoveTo(i32, i32),
+    RightClick,
+}
+
+/// Turns touchpad reports into mouse actions: a single finger moves the pointer to the same
+/// relative position on screen, a short two finger tap right-clicks.
+#[derive(Debug, Default)]
+pub struct TouchpadMouseState {
+    touch_started: Option<Instant>,
+    two_fingers: bool,
+}
+
+impl TouchpadMouseState {
+    pub fn update(&mut self, report: &DualSenseInputReport, now: Instant) -> Option<MouseAction> {
+        let [first, second] = report.sorted_touch_points();
+        let fingers = first.is_active() as u8 + second.is_active() as u8;
+
+        if fingers == 0 {
+            let started = self.touch_started.take()?;
+            let tapped = std::mem::take(&mut self.two_fingers)
+                && now.duration_since(started) <= TWO_FINGER_TAP_MAX;
+            return tapped.then_some(MouseAction::RightClick);
+        }
+
+        self.touch_started.get_or_insert(now);
+        if fingers == 2 {
+            self.two_fingers = true;
+            return None;
+        }
+        // lifting one finger of a two finger tap shouldn't jump the pointer
+        if self.two_fingers {
+            return None;
+        }
+
+        let (x, y) = first.position_f32()?;
+        Some(MouseAction::MoveTo(
+            to_absolute(x, DS_TOUCHPAD_WIDTH),
+            to_absolute(y, DS_TOUCHPAD_HEIGHT),
+        ))
+    }
+}
+
+fn to_absolute(value: f32, size: u16) -> i32 {
+    (value as f64 / (size - 1) as f64 * ABSOLUTE_MAX).round() as i32
+}
+
+/// Drives the Windows mouse cursor from the DualSense touchpad
+pub struct TouchpadMouse;
+
+impl TouchpadMouse {
+    /// Reads reports from `conn` until it fails, the returned task stops when dropped.
+    pub fn start(mut conn: DualSenseConnection) -> Task<()> {
+        smol::spawn(async move {
+            let mut state = TouchpadMouseState::default();
+            loop {
+                let report = match conn.read_input_report().await {
+                    Ok(report) => report,
+                    Err(err) => {
+                        tracing::debug!("Touchpad mouse stopped: {err}");
+                        return;
+                    }
+                };
+                if let Some(action) = state.update(&report, Instant::now()) {
+                    send(action);
+                }
+            }
+        })
+    }
+}
+
+fn send(action: MouseAction) {
+    let inputs = match action {
+        MouseAction::MoveTo(x, y) => {
+            vec![mouse_input(x, y, MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE)]
+        }
+        MouseAction::RightClick => vec![
+            mouse_input(0, 0, MOUSEEVENTF_RIGHTDOWN),
+            mouse_input(0, 0, MOUSEEVENTF_RIGHTUP),
+        ],
+    };
+
+    // SAFETY: the inputs are fully initialized and the size matches the INPUT struct
+    let sent = unsafe { SendInput(&inputs, size_of::<INPUT>() as i32) };
+    if sent as usize != inputs.len() {
+        tracing::warn!("SendInput dropped {} events", inputs.len() - sent as usize);
+    }
+}
+
+fn mouse_input(dx: i32, dy: i32, flags: MOUSE_EVENT_FLAGS) -> INPUT {
+    INPUT {
+        r#type: INPUT_MOUSE,
+        Anonymous: INPUT_0 {
+            mi: MOUSEINPUT {
+                dx,
+                dy,
+                mouseData: 0,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dualsense::proto::{DualSenseInputReportBuilder, DualSenseTouchPoint};
+
+    fn report(points: &[(u8, u16, u16)]) -> DualSenseInputReport {
+        let mut builder = DualSenseInputReportBuilder::new();
+        for (slot, (id, x, y)) in points.iter().enumerate() {
+            builder = builder.touch_point(slot, DualSenseTouchPoint::new(*id, *x, *y, true));
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn single_finger_moves_two_finger_tap_clicks() {
+        let mut state = TouchpadMouseState::default();
+        let start = Instant::now();
+
+        assert_eq!(
+            state.update(&report(&[(1, 1919, 0)]), start),
+            Some(MouseAction::MoveTo(65535, 0))
+        );
+        assert_eq!(state.update(&report(&[]), start), None);
+
+        assert_eq!(
+            state.update(&report(&[(2, 100, 100), (3, 900, 100)]), start),
+            None
+        );
+        // one finger lifts first, the pointer stays put
+        assert_eq!(state.update(&report(&[(3, 900, 100)]), start), None);
+        assert_eq!(
+            state.update(&report(&[]), start + Duration::from_millis(100)),
+            Some(MouseAction::RightClick)
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use wasm_bindgen::prelude::*;
+
+use super::proto::DualSenseInputReport;
+
+/// Input report as exposed to JavaScript, e.g. for reports received through WebHID.
+///
+/// WebHID strips the report id from `inputreport` events, so it has to be passed back in
+/// front of the data.
+#[wasm_bindgen(js_name = DualSenseInputReport)]
+pub struct WasmInputReport(DualSenseInputReport);
+
+#[wasm_bindgen(js_class = DualSenseInputReport)]
+impl WasmInputReport {
+    /// `undefined` if the bytes are not a USB or Bluetooth full input report
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(data: &[u8]) -> Option<WasmInputReport> {
+        DualSenseInputReport::parse(data).map(|report| Self(report.clone()))
+    }
+
+    #[wasm_bindgen(js_name = leftStickX)]
+    pub fn left_stick_x(&self) -> f32 {
+        self.0.left_stick().0
+    }
+
+    #[wasm_bindgen(js_name = leftStickY)]
+    pub fn left_stick_y(&self) -> f32 {
+        self.0.left_stick().1
+    }
+
+    #[wasm_bindgen(js_name = rightStickX)]
+    pub fn right_stick_x(&self) -> f32 {
+        self.0.right_stick().0
+    }
+
+    #[wasm_bindgen(js_name = rightStickY)]
+    pub fn right_stick_y(&self) -> f32 {
+        self.0.right_stick().1
+    }
+
+    #[wasm_bindgen(js_name = leftTrigger)]
+    pub fn left_trigger(&self) -> f32 {
+        self.0.left_trigger()
+    }
+
+    #[wasm_bindgen(js_name = rightTrigger)]
+    pub fn right_trigger(&self) -> f32 {
+        self.0.right_trigger()
+    }
+
+    #[wasm_bindgen(js_name = batteryPercent)]
+    pub fn battery_percent(&self) -> u8 {
+        self.0.battery().0
+    }
+
+    #[wasm_bindgen(js_name = isCharging)]
+    pub fn is_charging(&self) -> bool {
+        self.0.battery().1
+    }
+
+    /// Names of the pressed buttons, as in `Button`'s `Display`
+    #[wasm_bindgen(js_name = pressedButtons)]
+    pub fn pressed_buttons(&self) -> Vec<String> {
+        self.0
+            .pressed_buttons()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    pub fn dpad(&self) -> String {
+        self.0.dpad().to_string()
+    }
+
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_js_string(&self) -> String {
+        self.0.to_string()
+    }
+}
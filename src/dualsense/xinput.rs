@@ -0,0 +1,111 @@
+use super::proto::{Button, DualSenseInputReport};
+
+pub const XINPUT_GAMEPAD_DPAD_UP: u16 = 0x0001;
+pub const XINPUT_GAMEPAD_DPAD_DOWN: u16 = 0x0002;
+pub const XINPUT_GAMEPAD_DPAD_LEFT: u16 = 0x0004;
+pub const XINPUT_GAMEPAD_DPAD_RIGHT: u16 = 0x0008;
+pub const XINPUT_GAMEPAD_START: u16 = 0x0010;
+pub const XINPUT_GAMEPAD_BACK: u16 = 0x0020;
+pub const XINPUT_GAMEPAD_LEFT_THUMB: u16 = 0x0040;
+pub const XINPUT_GAMEPAD_RIGHT_THUMB: u16 = 0x0080;
+pub const XINPUT_GAMEPAD_LEFT_SHOULDER: u16 = 0x0100;
+pub const XINPUT_GAMEPAD_RIGHT_SHOULDER: u16 = 0x0200;
+/// Not part of the public XInput headers, but understood by ViGEm and most games
+pub const XINPUT_GAMEPAD_GUIDE: u16 = 0x0400;
+pub const XINPUT_GAMEPAD_A: u16 = 0x1000;
+pub const XINPUT_GAMEPAD_B: u16 = 0x2000;
+pub const XINPUT_GAMEPAD_X: u16 = 0x4000;
+pub const XINPUT_GAMEPAD_Y: u16 = 0x8000;
+
+const BUTTON_MAP: [(Button, u16); 11] = [
+    (Button::Cross, XINPUT_GAMEPAD_A),
+    (Button::Circle, XINPUT_GAMEPAD_B),
+    (Button::Square, XINPUT_GAMEPAD_X),
+    (Button::Triangle, XINPUT_GAMEPAD_Y),
+    (Button::L1, XINPUT_GAMEPAD_LEFT_SHOULDER),
+    (Button::R1, XINPUT_GAMEPAD_RIGHT_SHOULDER),
+    (Button::L3, XINPUT_GAMEPAD_LEFT_THUMB),
+    (Button::R3, XINPUT_GAMEPAD_RIGHT_THUMB),
+    (Button::Options, XINPUT_GAMEPAD_START),
+    (Button::Create, XINPUT_GAMEPAD_BACK),
+    (Button::PS, XINPUT_GAMEPAD_GUIDE),
+];
+
+/// Mirrors `XINPUT_GAMEPAD`, so it can be handed to `vigem-client` and similar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(C)]
+pub struct XInputState {
+    pub buttons: u16,
+    pub left_trigger: u8,
+    pub right_trigger: u8,
+    pub thumb_lx: i16,
+    pub thumb_ly: i16,
+    pub thumb_rx: i16,
+    pub thumb_ry: i16,
+}
+
+impl From<&DualSenseInputReport> for XInputState {
+    fn from(report: &DualSenseInputReport) -> Self {
+        let mut buttons = BUTTON_MAP
+            .iter()
+            .filter(|(button, _)| report.is_pressed(*button))
+            .fold(0, |acc, (_, mask)| acc | mask);
+
+        let dpad = report.dpad();
+        for (pressed, mask) in [
+            (dpad.up(), XINPUT_GAMEPAD_DPAD_UP),
+            (dpad.down(), XINPUT_GAMEPAD_DPAD_DOWN),
+            (dpad.left(), XINPUT_GAMEPAD_DPAD_LEFT),
+            (dpad.right(), XINPUT_GAMEPAD_DPAD_RIGHT),
+        ] {
+            if pressed {
+                buttons |= mask;
+            }
+        }
+
+        let (lx, ly, rx, ry) = report.sticks_raw();
+        Self {
+            buttons,
+            left_trigger: (report.left_trigger() * u8::MAX as f32).round() as u8,
+            right_trigger: (report.right_trigger() * u8::MAX as f32).round() as u8,
+            thumb_lx: scale_axis(lx),
+            // XInput Y axes point up, HID ones point down
+            thumb_ly: scale_axis(u8::MAX - ly),
+            thumb_rx: scale_axis(rx),
+            thumb_ry: scale_axis(u8::MAX - ry),
+        }
+    }
+}
+
+/// Maps `0..=255` onto the full `i16` range
+fn scale_axis(value: u8) -> i16 {
+    (value as i32 * 257 + i16::MIN as i32) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dualsense::proto::{Button, DPad, DualSenseInputReportBuilder};
+
+    #[test]
+    fn maps_buttons_and_axes() {
+        let report = DualSenseInputReportBuilder::new()
+            .left_stick(0x00, 0x00)
+            .right_stick(0xFF, 0xFF)
+            .left_trigger(0xFF)
+            .button(Button::Cross, true)
+            .button(Button::Options, true)
+            .dpad(DPad::Right)
+            .build();
+
+        let state = XInputState::from(&report);
+        assert_eq!(
+            state.buttons,
+            XINPUT_GAMEPAD_A | XINPUT_GAMEPAD_START | XINPUT_GAMEPAD_DPAD_RIGHT
+        );
+        assert_eq!(state.left_trigger, 255);
+        assert_eq!(state.right_trigger, 0);
+        assert_eq!((state.thumb_lx, state.thumb_ly), (i16::MIN, i16::MAX));
+        assert_eq!((state.thumb_rx, state.thumb_ry), (i16::MAX, i16::MIN));
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    io,
+    time::{Duration, SystemTime},
+};
+
+use serde::Deserialize;
+
+use crate::dualsense::proto::FirmwareVersion;
+
+/// Minimum time between two checks for a newer firmware
+pub const FIRMWARE_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Time allowed for the whole request, a stalled server would block a thread otherwise
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `{"latest": "0224"}`, the version in the hex form shown by `FirmwareVersion`
+#[derive(Deserialize)]
+struct LatestFirmware {
+    latest: String,
+}
+
+/// Fetches the latest known firmware version from `url`, e.g. `AppConfig::firmware_url`
+pub async fn fetch_latest_firmware(url: &str) -> io::Result<FirmwareVersion> {
+    let url = url.to_owned();
+    // ureq blocks, keep it off the executor
+    let body = smol::unblock(move || {
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .timeout_global(Some(FETCH_TIMEOUT))
+            .build()
+            .into();
+        agent
+            .get(&url)
+            .call()
+            .and_then(|mut response| response.body_mut().read_to_string())
+    })
+    .await
+    .map_err(io::Error::other)?;
+    parse_latest_firmware(&body)
+}
+
+fn parse_latest_firmware(body: &str) -> io::Result<FirmwareVersion> {
+    let firmware: LatestFirmware = serde_json::from_str(body)?;
+    firmware
+        .latest
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Whether `FIRMWARE_CHECK_INTERVAL` passed since `last_check`. Also due if there was no
+/// check yet, or if the clock was set back before the last one.
+pub fn is_check_due(last_check: Option<SystemTime>, now: SystemTime) -> bool {
+    last_check.is_none_or(|last_check| {
+        now.duration_since(last_check)
+            .unwrap_or(FIRMWARE_CHECK_INTERVAL)
+            >= FIRMWARE_CHECK_INTERVAL
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_latest() {
+        assert_eq!(
+            parse_latest_firmware(r#"{"latest": "0224"}"#).unwrap(),
+            FirmwareVersion(0x0224)
+        );
+        assert!(parse_latest_firmware(r#"{"latest": "new"}"#).is_err());
+        assert!(parse_latest_firmware("<html>").is_err());
+    }
+
+    #[test]
+    fn check_due_once_a_day() {
+        let now = SystemTime::now();
+        assert!(is_check_due(None, now));
+        assert!(!is_check_due(Some(now - Duration::from_secs(60 * 60)), now));
+        assert!(is_check_due(Some(now - FIRMWARE_CHECK_INTERVAL), now));
+        assert!(is_check_due(Some(now + Duration::from_secs(60)), now));
+    }
+}
@@ -0,0 +1,22 @@
+pub mod app_config;
+pub mod debounce;
+pub mod device_manager;
+pub mod dualsense;
+#[cfg(feature = "firmware_check")]
+pub mod firmware;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(any(
+    feature = "metrics",
+    feature = "websocket",
+    all(feature = "relay", unix)
+))]
+pub mod net;
+pub mod panic;
+pub mod platform;
+#[cfg(all(feature = "relay", unix))]
+pub mod relay;
+#[cfg(feature = "udp_broadcast")]
+pub mod udp;
+#[cfg(feature = "websocket")]
+pub mod websocket;
@@ -1,10 +1,64 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod device_manager;
-mod dualsense;
 mod tray_icon;
+#[cfg(feature = "visualizer")]
+mod visualizer;
 
 fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+    if std::env::args().any(|arg| arg == "--latency-test") {
+        return smol::block_on(latency_test());
+    }
     tray_icon::run_tray_icon()?;
     Ok(())
 }
+
+/// Where `--latency-test` writes its results, release builds on Windows have no console
+const LATENCY_RESULTS_FILE: &str = "dualsense-latency.txt";
+
+/// Prints the rumble latency of the first controller found, and writes it to
+/// `LATENCY_RESULTS_FILE` in the working directory
+async fn latency_test() -> anyhow::Result<()> {
+    let results = match measure_latency().await {
+        Ok(results) => results,
+        Err(err) => format!("Latency test failed: {err:#}\n"),
+    };
+    print!("{results}");
+    std::fs::write(LATENCY_RESULTS_FILE, results)?;
+    Ok(())
+}
+
+async fn measure_latency() -> anyhow::Result<String> {
+    use std::fmt::Write;
+
+    use async_hid::HidBackend;
+    use dualsense_tray::dualsense::{
+        async_hid::DualSense,
+        latency::{DEFAULT_LATENCY_SAMPLES, measure_round_trip},
+    };
+    use futures_lite::StreamExt;
+
+    let hid = HidBackend::default();
+    let Some(device) = DualSense::enumerate(&hid).await?.next().await else {
+        anyhow::bail!("No DualSense controller found");
+    };
+    let ds = DualSense::open_device(device).await?;
+    let mut conn = ds.connect().await?;
+
+    println!(
+        "Measuring {DEFAULT_LATENCY_SAMPLES} rumble pulses on {}, keep it still",
+        ds.name()
+    );
+    let samples = measure_round_trip(&mut conn, DEFAULT_LATENCY_SAMPLES).await?;
+    let mut results = String::new();
+    writeln!(
+        results,
+        "{} of {DEFAULT_LATENCY_SAMPLES} pulses felt on {}",
+        samples.len(),
+        ds.name()
+    )?;
+    writeln!(results, "mean: {:.1}ms", samples.mean_ms())?;
+    writeln!(results, "p95:  {:.1}ms", samples.p95_ms())?;
+    writeln!(results, "p99:  {:.1}ms", samples.p99_ms())?;
+    Ok(results)
+}
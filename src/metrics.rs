@@ -0,0 +1,168 @@
+use std::{collections::HashMap, io, net::SocketAddr, sync::Mutex};
+
+use async_hid::DeviceId;
+use futures_lite::{AsyncReadExt, AsyncWriteExt};
+use prometheus::{IntGaugeVec, Opts, Registry, TextEncoder};
+use smol::{
+    Task,
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    device_manager::DeviceManagerEvent,
+    net::{TcpServer, accept_with_backoff},
+};
+
+/// Serves battery and charging gauges in the Prometheus text format
+pub struct MetricsServer {
+    battery_percent: IntGaugeVec,
+    charging: IntGaugeVec,
+    serials: Mutex<HashMap<DeviceId, String>>,
+    local_addr: SocketAddr,
+    _task: Task<()>,
+}
+
+impl MetricsServer {
+    /// Listens on `addr`, any request path is answered with the metrics. Use a loopback
+    /// address unless the metrics should be scraped from other hosts.
+    pub async fn start(addr: SocketAddr) -> io::Result<Self> {
+        let registry = Registry::new();
+        let battery_percent = IntGaugeVec::new(
+            Opts::new("dualsense_battery_percent", "Battery level in percent"),
+            &["serial"],
+        )
+        .map_err(io::Error::other)?;
+        let charging = IntGaugeVec::new(
+            Opts::new("dualsense_charging", "1 while the battery is charging"),
+            &["serial"],
+        )
+        .map_err(io::Error::other)?;
+        registry
+            .register(Box::new(battery_percent.clone()))
+            .map_err(io::Error::other)?;
+        registry
+            .register(Box::new(charging.clone()))
+            .map_err(io::Error::other)?;
+
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let task = smol::spawn(serve(listener, registry));
+
+        Ok(Self {
+            battery_percent,
+            charging,
+            serials: Mutex::new(HashMap::new()),
+            local_addr,
+            _task: task,
+        })
+    }
+
+    pub fn observe(&self, event: &DeviceManagerEvent) {
+        match event {
+            DeviceManagerEvent::Connected { id, serial, .. } => {
+                // devices without a serial are labeled by their id
+                let serial = serial.clone().unwrap_or_else(|| format!("{id:?}"));
+                self.serials.lock().unwrap().insert(id.clone(), serial);
+            }
+            DeviceManagerEvent::Disconnected(id) => {
+                if let Some(serial) = self.serials.lock().unwrap().remove(id) {
+                    let _ = self.battery_percent.remove_label_values(&[&serial]);
+                    let _ = self.charging.remove_label_values(&[&serial]);
+                }
+            }
+            DeviceManagerEvent::BatteryUpdate(id, (capacity, charging)) => {
+                let Some(serial) = self.serials.lock().unwrap().get(id).cloned() else {
+                    return;
+                };
+                self.battery_percent
+                    .with_label_values(&[&serial])
+                    .set(*capacity as i64);
+                self.charging
+                    .with_label_values(&[&serial])
+                    .set(*charging as i64);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl TcpServer for MetricsServer {
+    fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+async fn serve(listener: TcpListener, registry: Registry) {
+    loop {
+        let stream = accept_with_backoff(|| listener.accept(), "metrics request").await;
+        let registry = registry.clone();
+        smol::spawn(async move {
+            let _ = respond(stream, &registry).await;
+        })
+        .detach();
+    }
+}
+
+async fn respond(mut stream: TcpStream, registry: &Registry) -> io::Result<()> {
+    // the request itself is not inspected
+    let mut buf = [0u8; 1024];
+    let _ = stream.read(&mut buf).await?;
+
+    let body = TextEncoder::new()
+        .encode_to_string(&registry.gather())
+        .map_err(io::Error::other)?;
+    let head = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.flush().await
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use macro_rules_attribute::apply;
+    use smol_macros::test;
+
+    use super::*;
+    use crate::dualsense::async_hid::DualSenseConnectionType;
+
+    async fn scrape(addr: SocketAddr) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[apply(test!)]
+    async fn scrape_battery_gauges() {
+        let metrics = MetricsServer::start(([127, 0, 0, 1], 0).into())
+            .await
+            .unwrap();
+        let id = DeviceId::DevPath("/dev/hidraw0".into());
+        metrics.observe(&DeviceManagerEvent::Connected {
+            id: id.clone(),
+            name: "DualSense USB".to_string(),
+            serial: Some("abc".to_string()),
+            connection_type: DualSenseConnectionType::USB,
+        });
+        metrics.observe(&DeviceManagerEvent::BatteryUpdate(id.clone(), (80, true)));
+
+        let response = scrape(metrics.local_addr()).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("dualsense_battery_percent{serial=\"abc\"} 80\n"));
+        assert!(response.contains("dualsense_charging{serial=\"abc\"} 1\n"));
+
+        metrics.observe(&DeviceManagerEvent::Disconnected(id));
+        assert!(
+            !scrape(metrics.local_addr())
+                .await
+                .contains("serial=\"abc\"")
+        );
+    }
+}
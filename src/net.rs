@@ -0,0 +1,30 @@
+use std::{future::Future, io, time::Duration};
+
+use async_io::Timer;
+
+/// Wait after a failed accept, e.g. while out of file descriptors
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// A server listening on a TCP port
+#[cfg(any(feature = "metrics", feature = "websocket"))]
+pub trait TcpServer {
+    /// The address bound, with the actual port when started on port 0
+    fn local_addr(&self) -> std::net::SocketAddr;
+}
+
+/// Runs `accept` until it succeeds, e.g. `|| listener.accept()` of a TCP or a Unix listener.
+/// Failures are logged as failing to accept a `client` and retried after a short wait.
+pub(crate) async fn accept_with_backoff<S, A, F>(mut accept: impl FnMut() -> F, client: &str) -> S
+where
+    F: Future<Output = io::Result<(S, A)>>,
+{
+    loop {
+        match accept().await {
+            Ok((stream, _)) => return stream,
+            Err(err) => {
+                tracing::warn!("Failed to accept a {client}: {err}");
+                Timer::after(ACCEPT_ERROR_BACKOFF).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::{cell::Cell, panic};
+
+thread_local! {
+    /// Depth of `without_reset` calls on this thread
+    static HANDLED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Runs `reset` before the default panic output, e.g. to stop rumble and trigger effects
+/// that would otherwise persist until the controller is disconnected.
+///
+/// `reset` runs on a thread of its own and must not depend on the async runtime making
+/// progress, as the panicking thread may be one of its workers.
+pub fn set_reset_hook<F>(reset: F)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        reset_unless_handled(&reset);
+        default(info);
+    }));
+}
+
+/// The hook installed by `set_reset_hook`, before the default panic output
+fn reset_unless_handled<F>(reset: &F)
+where
+    F: Fn() + Sync,
+{
+    if HANDLED.with(Cell::get) == 0 {
+        std::thread::scope(|scope| {
+            // a panic while resetting is swallowed, the original one is more relevant. The
+            // reset thread has a depth of its own, without it that panic would reset again.
+            let _ = scope.spawn(|| without_reset(reset)).join();
+        });
+    }
+}
+
+/// Runs `f` without the reset hook, for panics that are caught and handled by the caller.
+/// The default panic output is still printed.
+pub fn without_reset<R>(f: impl FnOnce() -> R) -> R {
+    struct Guard;
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            HANDLED.with(|depth| depth.set(depth.get() - 1));
+        }
+    }
+
+    HANDLED.with(|depth| depth.set(depth.get() + 1));
+    let _guard = Guard;
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    // the hook body is mostly tested directly, the process-wide hook also sees panics of
+    // tests running in parallel
+    #[test]
+    fn reset_skipped_when_handled() {
+        let resets = AtomicUsize::new(0);
+        let reset = || {
+            resets.fetch_add(1, Ordering::SeqCst);
+        };
+
+        reset_unless_handled(&reset);
+        without_reset(|| without_reset(|| reset_unless_handled(&reset)));
+        assert_eq!(resets.load(Ordering::SeqCst), 1);
+
+        // the depth is restored when the handled closure panics
+        let handled = panic::catch_unwind(|| without_reset(|| panic!("handled")));
+        assert!(handled.is_err());
+        reset_unless_handled(&reset);
+        assert_eq!(resets.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn panicking_reset_is_swallowed() {
+        reset_unless_handled(&|| panic!("reset"));
+    }
+
+    #[test]
+    fn panicking_reset_through_hook() {
+        static RESETS: AtomicUsize = AtomicUsize::new(0);
+        // stays installed for the remaining tests, which only makes their panics louder
+        set_reset_hook(|| {
+            RESETS.fetch_add(1, Ordering::SeqCst);
+            panic!("reset");
+        });
+
+        let result = std::thread::spawn(|| panic!("unhandled")).join();
+        assert!(result.is_err());
+        // the panicking reset didn't run the hook again, that would never return
+        assert!(RESETS.load(Ordering::SeqCst) >= 1);
+    }
+}
@@ -0,0 +1,33 @@
+use std::{fs, io, path::Path};
+
+use crate::dualsense::proto::{DUALSENSE_ACCESS_PID, DUALSENSE_EDGE_PID, DUALSENSE_PID, SONY_VID};
+
+pub const DEFAULT_UDEV_RULES_PATH: &str = "/etc/udev/rules.d/70-dualsense.rules";
+
+const PRODUCT_IDS: [u16; 3] = [DUALSENSE_PID, DUALSENSE_EDGE_PID, DUALSENSE_ACCESS_PID];
+
+/// Rules granting the `input` group access to the hidraw nodes of every DualSense variant
+pub fn udev_rules() -> String {
+    let mut rules = String::from("# DualSense controllers, generated by dualsense-setup\n");
+    for pid in PRODUCT_IDS {
+        // USB devices expose their ids as attributes of a parent device
+        rules.push_str(&format!(
+            "SUBSYSTEM==\"hidraw\", ATTRS{{idVendor}}==\"{SONY_VID:04x}\", ATTRS{{idProduct}}==\"{pid:04x}\", GROUP=\"input\", MODE=\"0660\"\n"
+        ));
+        // Bluetooth devices only show them in the HID device name
+        rules.push_str(&format!(
+            "SUBSYSTEM==\"hidraw\", KERNELS==\"*{SONY_VID:04X}:{pid:04X}*\", GROUP=\"input\", MODE=\"0660\"\n"
+        ));
+    }
+    rules
+}
+
+pub fn write_udev_rules(path: &Path) -> io::Result<()> {
+    fs::write(path, udev_rules())
+}
+
+/// Path of the `index`th hidraw node. The numbering is shared with every other HID device,
+/// so this is only a DualSense when the system is set up that way, e.g. on a CI runner.
+pub fn platform_hid_path(index: usize) -> String {
+    format!("/dev/hidraw{index}")
+}
@@ -0,0 +1,241 @@
+use std::{
+    fs, io,
+    os::unix::fs::FileTypeExt,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use async_hid::DeviceId;
+use futures_lite::{AsyncBufReadExt, AsyncWriteExt, Stream, StreamExt, io::BufReader};
+use serde::{Deserialize, Serialize};
+use smol::{
+    Task,
+    channel::{self, Receiver, Sender, TrySendError},
+    net::unix::{UnixListener, UnixStream},
+};
+
+use crate::{
+    device_manager::{DeviceManager, DeviceManagerEvent},
+    net::accept_with_backoff,
+};
+
+/// Events queued per client, events to a client that falls behind are dropped
+const CLIENT_QUEUE: usize = 64;
+
+/// `DeviceManagerEvent` as sent over the relay. Device ids are platform specific and can't
+/// be rebuilt on the other side, so they are sent as their debug representation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RelayEvent {
+    Connected {
+        device: String,
+        name: String,
+        serial: Option<String>,
+        connection_type: String,
+    },
+    Disconnected {
+        device: String,
+    },
+    BatteryUpdate {
+        device: String,
+        capacity: u8,
+        charging: bool,
+    },
+    DeviceIdle {
+        device: String,
+    },
+    DeviceActive {
+        device: String,
+    },
+    StatsUpdate {
+        device: String,
+        report_rate_hz: f32,
+        p95_lag_ms: f32,
+    },
+    Error {
+        message: String,
+    },
+    PlayerNumberAssigned {
+        device: String,
+        player_number: u8,
+    },
+    HealthCheckFailed {
+        device: String,
+    },
+    FirmwareUpdateAvailable {
+        device: String,
+        version: String,
+    },
+}
+
+impl From<&DeviceManagerEvent> for RelayEvent {
+    fn from(event: &DeviceManagerEvent) -> Self {
+        let device = |id: &DeviceId| format!("{id:?}");
+        match event {
+            DeviceManagerEvent::Connected {
+                id,
+                name,
+                serial,
+                connection_type,
+            } => Self::Connected {
+                device: device(id),
+                name: name.clone(),
+                serial: serial.clone(),
+                connection_type: connection_type.to_string(),
+            },
+            DeviceManagerEvent::Disconnected(id) => Self::Disconnected { device: device(id) },
+            DeviceManagerEvent::BatteryUpdate(id, (capacity, charging)) => Self::BatteryUpdate {
+                device: device(id),
+                capacity: *capacity,
+                charging: *charging,
+            },
+            DeviceManagerEvent::DeviceIdle(id) => Self::DeviceIdle { device: device(id) },
+            DeviceManagerEvent::DeviceActive(id) => Self::DeviceActive { device: device(id) },
+            DeviceManagerEvent::StatsUpdate {
+                id,
+                report_rate_hz,
+                p95_lag_ms,
+            } => Self::StatsUpdate {
+                device: device(id),
+                report_rate_hz: *report_rate_hz,
+                p95_lag_ms: *p95_lag_ms,
+            },
+            DeviceManagerEvent::Error(message) => Self::Error {
+                message: message.clone(),
+            },
+            DeviceManagerEvent::PlayerNumberAssigned(id, player_number) => {
+                Self::PlayerNumberAssigned {
+                    device: device(id),
+                    player_number: *player_number,
+                }
+            }
+            DeviceManagerEvent::HealthCheckFailed(id) => {
+                Self::HealthCheckFailed { device: device(id) }
+            }
+            DeviceManagerEvent::FirmwareUpdateAvailable(id, version) => {
+                Self::FirmwareUpdateAvailable {
+                    device: device(id),
+                    version: version.to_string(),
+                }
+            }
+        }
+    }
+}
+
+type Clients = Arc<Mutex<Vec<Sender<Arc<str>>>>>;
+
+/// Forwards the events of a `DeviceManager` as newline delimited JSON to every client of a
+/// Unix socket, e.g. to a game running in a container.
+pub struct RelayServer {
+    _accept_task: Task<()>,
+    _forward_task: Task<()>,
+}
+
+impl RelayServer {
+    /// A socket left behind at `socket_path` by a previous run is replaced.
+    pub async fn start(socket_path: &Path, manager: Arc<DeviceManager>) -> io::Result<Self> {
+        Self::start_with_events(socket_path, manager.subscribe())
+    }
+
+    fn start_with_events(
+        socket_path: &Path,
+        events: Receiver<DeviceManagerEvent>,
+    ) -> io::Result<Self> {
+        if fs::symlink_metadata(socket_path).is_ok_and(|meta| meta.file_type().is_socket()) {
+            fs::remove_file(socket_path)?;
+        }
+        let listener = UnixListener::bind(socket_path)?;
+        let clients: Clients = Arc::new(Mutex::new(Vec::new()));
+
+        Ok(Self {
+            _accept_task: smol::spawn(accept(listener, clients.clone())),
+            _forward_task: smol::spawn(forward(events, clients)),
+        })
+    }
+}
+
+async fn accept(listener: UnixListener, clients: Clients) {
+    loop {
+        let stream = accept_with_backoff(|| listener.accept(), "relay client").await;
+        let (sender, receiver) = channel::bounded(CLIENT_QUEUE);
+        clients.lock().unwrap().push(sender);
+        smol::spawn(async move {
+            if let Err(err) = serve_client(stream, receiver).await {
+                tracing::debug!("Relay client disconnected: {err}");
+            }
+        })
+        .detach();
+    }
+}
+
+async fn serve_client(mut stream: UnixStream, lines: Receiver<Arc<str>>) -> io::Result<()> {
+    // the sender is dropped from `clients` once the receiver is gone
+    while let Ok(line) = lines.recv().await {
+        stream.write_all(line.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+async fn forward(events: Receiver<DeviceManagerEvent>, clients: Clients) {
+    while let Ok(event) = events.recv().await {
+        let Ok(mut line) = serde_json::to_string(&RelayEvent::from(&event)) else {
+            continue;
+        };
+        line.push('\n');
+        let line: Arc<str> = line.into();
+        clients.lock().unwrap().retain(|client| {
+            !matches!(client.try_send(line.clone()), Err(TrySendError::Closed(_)))
+        });
+    }
+}
+
+/// Receives the events of a `RelayServer`
+pub struct RelayClient;
+
+impl RelayClient {
+    /// Lines that are not valid events are skipped, the stream ends when the server goes away.
+    pub async fn connect(socket_path: &Path) -> io::Result<impl Stream<Item = RelayEvent>> {
+        let stream = UnixStream::connect(socket_path).await?;
+        Ok(BufReader::new(stream)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok()))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use macro_rules_attribute::apply;
+    use smol_macros::test;
+
+    use super::*;
+
+    #[apply(test!)]
+    async fn relays_events_to_clients() {
+        let path =
+            std::env::temp_dir().join(format!("dualsense-relay-{}.sock", std::process::id()));
+        let (events, receiver) = channel::unbounded();
+        let _server = RelayServer::start_with_events(&path, receiver).unwrap();
+
+        let mut first = Box::pin(RelayClient::connect(&path).await.unwrap());
+        let second = RelayClient::connect(&path).await.unwrap();
+        drop(second);
+        // let the server register both clients
+        smol::Timer::after(std::time::Duration::from_millis(50)).await;
+
+        let id = DeviceId::DevPath("/dev/hidraw0".into());
+        events
+            .send(DeviceManagerEvent::BatteryUpdate(id, (70, true)))
+            .await
+            .unwrap();
+        assert_eq!(
+            first.next().await,
+            Some(RelayEvent::BatteryUpdate {
+                device: r#"DevPath("/dev/hidraw0")"#.to_string(),
+                capacity: 70,
+                charging: true,
+            })
+        );
+        let _ = fs::remove_file(&path);
+    }
+}
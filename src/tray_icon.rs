@@ -1,6 +1,13 @@
-use std::{collections::HashMap, sync::Arc};
+#[cfg(feature = "firmware_check")]
+use std::time::SystemTime;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_hid::DeviceId;
+use futures_lite::FutureExt;
 use image::imageops::FilterType;
 use tao::{
     event::Event,
@@ -11,14 +18,40 @@ use tray_icon::{
     menu::{AboutMetadata, Menu, MenuEvent, MenuItem, PredefinedMenuItem},
 };
 
-use crate::device_manager::{DeviceManager, DeviceManagerEvent};
+#[cfg(any(feature = "metrics", feature = "firmware_check"))]
+use dualsense_tray::app_config::AppConfig;
+#[cfg(feature = "visualizer")]
+use dualsense_tray::dualsense::proto::DualSenseInputReport;
+#[cfg(feature = "firmware_check")]
+use dualsense_tray::firmware::{fetch_latest_firmware, is_check_due};
+#[cfg(feature = "metrics")]
+use dualsense_tray::metrics::MetricsServer;
+use dualsense_tray::{
+    device_manager::{DeviceManager, DeviceManagerEvent},
+    dualsense::proto::{DualSenseOutputReportBuilder, FirmwareVersion, TriggerEffect},
+};
+#[cfg(feature = "visualizer")]
+use tao::{event::WindowEvent, event_loop::EventLoopProxy};
+
+#[cfg(feature = "visualizer")]
+use crate::visualizer::Visualizer;
 
 enum UserEvent {
     TrayIconEvent(tray_icon::TrayIconEvent),
     MenuEvent(tray_icon::menu::MenuEvent),
     Device(DeviceManagerEvent),
+    #[cfg(feature = "visualizer")]
+    InputReport(DualSenseInputReport),
 }
 
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(30);
+const TRIGGER_TEST_DURATION: Duration = Duration::from_secs(2);
+const PANIC_RESET_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often the config is looked at to see whether a firmware check is due. Failed checks
+/// are retried after it.
+#[cfg(feature = "firmware_check")]
+const FIRMWARE_CHECK_POLL_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 pub fn run_tray_icon() -> anyhow::Result<()> {
     let event_loop = EventLoopBuilder::<UserEvent>::with_user_event().build();
 
@@ -34,25 +67,71 @@ pub fn run_tray_icon() -> anyhow::Result<()> {
         let _ = proxy.send_event(UserEvent::MenuEvent(event));
     }));
 
+    #[cfg(feature = "metrics")]
+    let metrics = {
+        let addr = AppConfig::load().metrics_addr;
+        match smol::block_on(MetricsServer::start(addr)) {
+            Ok(metrics) => Some(metrics),
+            Err(err) => {
+                tracing::warn!("Failed to start the metrics server on {addr}: {err}");
+                None
+            }
+        }
+    };
+
     let mut device_manager = DeviceManager::new();
     let proxy = event_loop.create_proxy();
     device_manager.set_event_handler(move |event| {
         println!("{:?}", event);
+        #[cfg(feature = "metrics")]
+        {
+            if let Some(metrics) = &metrics {
+                metrics.observe(&event);
+            }
+        }
         let _ = proxy.send_event(UserEvent::Device(event));
     });
     let device_manager = Arc::new(device_manager);
+    dualsense_tray::panic::set_reset_hook({
+        let device_manager = device_manager.clone();
+        move || {
+            // a lock may be held by the panicking thread, don't wait on it forever
+            smol::block_on(device_manager.shutdown().or(async {
+                smol::Timer::after(PANIC_RESET_TIMEOUT).await;
+            }))
+        }
+    });
     let _dm_task = {
         let device_manager = device_manager.clone();
         smol::spawn(async move {
-            let _ = device_manager.open_all_devices().await;
-            let _ = device_manager.watch_pnp().await;
+            device_manager
+                .run(async {
+                    let _ = device_manager.open_all_devices().await;
+                    let _ = device_manager.watch_pnp().await;
+                })
+                .await
+        })
+    };
+    let _status_task = {
+        let device_manager = device_manager.clone();
+        smol::spawn(async move {
+            device_manager
+                .run(device_manager.watch_status(STATUS_POLL_INTERVAL))
+                .await
         })
     };
+    #[cfg(feature = "firmware_check")]
+    let _firmware_task = watch_firmware_updates(device_manager.clone());
 
     let tray_menu = Menu::new();
 
+    let recalibrate_i = MenuItem::new("Recalibrate sticks", true, None);
+    let trigger_test_i = MenuItem::new("Test trigger feedback", true, None);
     let quit_i = MenuItem::new("Quit", true, None);
     let _ = tray_menu.append_items(&[
+        &PredefinedMenuItem::separator(),
+        &recalibrate_i,
+        &trigger_test_i,
         &PredefinedMenuItem::separator(),
         &PredefinedMenuItem::about(
             None,
@@ -67,15 +146,25 @@ pub fn run_tray_icon() -> anyhow::Result<()> {
     ]);
 
     let mut device_info: HashMap<DeviceId, (String, (u8, bool))> = HashMap::new();
+    let mut idle_devices: HashSet<DeviceId> = HashSet::new();
+    let mut report_rates: HashMap<DeviceId, (f32, f32)> = HashMap::new();
+    let mut connected_at: HashMap<DeviceId, Instant> = HashMap::new();
+    let mut player_numbers: HashMap<DeviceId, u8> = HashMap::new();
+    let mut firmware_updates: HashMap<DeviceId, FirmwareVersion> = HashMap::new();
+    let mut last_error: Option<String> = None;
     let mut device_info_i: Vec<MenuItem> = Vec::new();
     let mut redraw_device_info = false;
 
     let mut tray_icon = None;
+    #[cfg(feature = "visualizer")]
+    let mut visualizer: Option<(Visualizer, smol::Task<()>)> = None;
+    #[cfg(feature = "visualizer")]
+    let proxy = event_loop.create_proxy();
 
     let _menu_channel = MenuEvent::receiver();
     let _tray_channel = TrayIconEvent::receiver();
 
-    event_loop.run(move |event, _, control_flow| {
+    event_loop.run(move |event, _target, control_flow| {
         *control_flow = ControlFlow::Wait;
 
         match event {
@@ -111,23 +200,80 @@ pub fn run_tray_icon() -> anyhow::Result<()> {
                     let device_manager = device_manager.clone();
                     smol::spawn(async move { device_manager.update_status().await }).detach();
                 }
+                #[cfg(feature = "visualizer")]
+                TrayIconEvent::DoubleClick { .. } if visualizer.is_none() => {
+                    match Visualizer::open(_target) {
+                        Ok(window) => {
+                            let task = stream_input_reports(device_manager.clone(), proxy.clone());
+                            visualizer = Some((window, task));
+                        }
+                        Err(err) => tracing::warn!("Failed to open the visualizer: {err}"),
+                    }
+                }
                 _ => {}
             },
 
+            #[cfg(feature = "visualizer")]
+            Event::UserEvent(UserEvent::InputReport(report)) => {
+                if let Some((window, _)) = &mut visualizer {
+                    window.set_report(report);
+                }
+            }
+
+            #[cfg(feature = "visualizer")]
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Focused(false) | WindowEvent::CloseRequested,
+                ..
+            } if visualizer
+                .as_ref()
+                .is_some_and(|(window, _)| window.window_id() == window_id) =>
+            {
+                // dropping the task stops reading reports
+                visualizer = None;
+            }
+
+            #[cfg(feature = "visualizer")]
+            Event::RedrawRequested(window_id) => {
+                if let Some((window, _)) = &mut visualizer
+                    && window.window_id() == window_id
+                    && let Err(err) = window.redraw()
+                {
+                    tracing::warn!("Failed to draw the visualizer: {err}");
+                }
+            }
+
             Event::UserEvent(UserEvent::MenuEvent(event)) => {
                 if event.id == quit_i.id() {
+                    smol::block_on(device_manager.shutdown());
                     tray_icon.take();
                     *control_flow = ControlFlow::Exit;
+                } else if event.id == recalibrate_i.id() {
+                    let device_manager = device_manager.clone();
+                    smol::spawn(async move { device_manager.recalibrate_sticks().await }).detach();
+                } else if event.id == trigger_test_i.id() {
+                    let device_manager = device_manager.clone();
+                    let device_ids = device_info.keys().cloned().collect::<Vec<_>>();
+                    smol::spawn(
+                        async move { test_trigger_feedback(&device_manager, device_ids).await },
+                    )
+                    .detach();
                 }
             }
 
             Event::UserEvent(UserEvent::Device(event)) => match event {
-                DeviceManagerEvent::Connected(device_id, name) => {
-                    device_info.insert(device_id, (name, (0, false)));
+                DeviceManagerEvent::Connected { id, name, .. } => {
+                    connected_at.entry(id.clone()).or_insert_with(Instant::now);
+                    device_info.insert(id, (name, (0, false)));
                     redraw_device_info = true;
                 }
                 DeviceManagerEvent::Disconnected(device_id) => {
                     device_info.remove(&device_id);
+                    idle_devices.remove(&device_id);
+                    report_rates.remove(&device_id);
+                    connected_at.remove(&device_id);
+                    player_numbers.remove(&device_id);
+                    firmware_updates.remove(&device_id);
                     redraw_device_info = true;
                 }
                 DeviceManagerEvent::BatteryUpdate(device_id, status_update) => {
@@ -139,6 +285,40 @@ pub fn run_tray_icon() -> anyhow::Result<()> {
                         redraw_device_info = true;
                     }
                 }
+                DeviceManagerEvent::DeviceIdle(device_id) => {
+                    idle_devices.insert(device_id);
+                    redraw_device_info = true;
+                }
+                DeviceManagerEvent::DeviceActive(device_id) => {
+                    idle_devices.remove(&device_id);
+                    redraw_device_info = true;
+                }
+                DeviceManagerEvent::StatsUpdate {
+                    id,
+                    report_rate_hz,
+                    p95_lag_ms,
+                } => {
+                    report_rates.insert(id, (report_rate_hz, p95_lag_ms));
+                    redraw_device_info = true;
+                }
+                DeviceManagerEvent::Error(message) => {
+                    last_error = Some(message);
+                    redraw_device_info = true;
+                }
+                DeviceManagerEvent::PlayerNumberAssigned(device_id, player_number) => {
+                    player_numbers.insert(device_id, player_number);
+                    redraw_device_info = true;
+                }
+                DeviceManagerEvent::HealthCheckFailed(device_id) => {
+                    if let Some((name, _)) = device_info.get(&device_id) {
+                        last_error = Some(format!("{name} is not responding"));
+                        redraw_device_info = true;
+                    }
+                }
+                DeviceManagerEvent::FirmwareUpdateAvailable(device_id, version) => {
+                    firmware_updates.insert(device_id, version);
+                    redraw_device_info = true;
+                }
             },
 
             Event::MainEventsCleared => {
@@ -150,8 +330,11 @@ pub fn run_tray_icon() -> anyhow::Result<()> {
                         let _ = tray_menu.remove(&i);
                     }
 
-                    for (i, info) in device_info.values().enumerate() {
-                        let label = format!("{}. {}", i + 1, info.0);
+                    for (i, (device_id, info)) in device_info.iter().enumerate() {
+                        let label = match player_numbers.get(device_id) {
+                            Some(player_number) => format!("Player {player_number}: {}", info.0),
+                            None => format!("{}. {}", i + 1, info.0),
+                        };
                         let status = if &info.1.0 == &0 {
                             "Unknown".to_string()
                         } else if info.1.1 {
@@ -159,10 +342,56 @@ pub fn run_tray_icon() -> anyhow::Result<()> {
                         } else {
                             format!("{}%", info.1.0)
                         };
+                        let status = if idle_devices.contains(device_id) {
+                            format!("{status}, idle")
+                        } else {
+                            status
+                        };
+                        let status = match report_rates.get(device_id) {
+                            Some((rate, p95)) => {
+                                format!("{status}, {rate:.0} Hz, p95 latency: {p95:.0}ms")
+                            }
+                            None => status,
+                        };
+                        let status = match connected_at.get(device_id) {
+                            Some(since) => {
+                                format!("{status}, connected {}", format_uptime(since.elapsed()))
+                            }
+                            None => status,
+                        };
+                        let status = match firmware_updates.get(device_id) {
+                            Some(version) => format!("{status}, firmware {version} available"),
+                            None => status,
+                        };
                         let item = MenuItem::new(&format!("{label} ({status})"), false, None);
                         let _ = tray_menu.insert(&item, i);
                         device_info_i.push(item);
                     }
+
+                    let tooltip = device_info
+                        .iter()
+                        .map(|(device_id, (name, (capacity, charging)))| {
+                            let battery = format_battery_bar(*capacity, *charging);
+                            if firmware_updates.contains_key(device_id) {
+                                format!("{name} {battery}, firmware update available")
+                            } else {
+                                format!("{name} {battery}")
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    if let Some(tray_icon) = &tray_icon {
+                        let tooltip = if tooltip.is_empty() {
+                            "DualSense".to_string()
+                        } else {
+                            tooltip
+                        };
+                        let tooltip = match &last_error {
+                            Some(error) => format!("{tooltip}\nError: {error}"),
+                            None => tooltip,
+                        };
+                        let _ = tray_icon.set_tooltip(Some(tooltip));
+                    }
                 }
             }
 
@@ -171,6 +400,125 @@ pub fn run_tray_icon() -> anyhow::Result<()> {
     })
 }
 
+/// Fetches the latest firmware version at most once per `FIRMWARE_CHECK_INTERVAL`, keeping
+/// it and the time of the check in the config file, and hands it to the device manager, which
+/// emits `FirmwareUpdateAvailable` for outdated controllers. Nothing is fetched while the
+/// config has no `firmware_url`.
+#[cfg(feature = "firmware_check")]
+fn watch_firmware_updates(device_manager: Arc<DeviceManager>) -> smol::Task<()> {
+    smol::spawn(async move {
+        loop {
+            let mut config = AppConfig::load();
+            if let Some(url) = config.firmware_url.clone()
+                && is_check_due(config.last_firmware_check, SystemTime::now())
+            {
+                match fetch_latest_firmware(&url).await {
+                    Ok(latest) => {
+                        config.latest_firmware = Some(latest);
+                        config.last_firmware_check = Some(SystemTime::now());
+                        if let Err(err) = config.save() {
+                            tracing::warn!("Failed to save the config: {err}");
+                        }
+                    }
+                    Err(err) => {
+                        tracing::warn!("Failed to fetch the latest firmware version: {err}")
+                    }
+                }
+            }
+            if let Some(latest) = config.latest_firmware {
+                device_manager.set_latest_firmware(latest).await;
+            }
+            smol::Timer::after(FIRMWARE_CHECK_POLL_INTERVAL).await;
+        }
+    })
+}
+
+/// Forwards the input reports of the first opened controller to the event loop, until the
+/// controller fails or the task is dropped
+#[cfg(feature = "visualizer")]
+fn stream_input_reports(
+    device_manager: Arc<DeviceManager>,
+    proxy: EventLoopProxy<UserEvent>,
+) -> smol::Task<()> {
+    smol::spawn(async move {
+        let Some((_, device)) = device_manager.opened_devices().await.into_iter().next() else {
+            return;
+        };
+        let mut conn = match device.connect().await {
+            Ok(conn) => conn,
+            Err(err) => {
+                tracing::warn!("Failed to connect to {}: {err}", device.name());
+                return;
+            }
+        };
+        while let Ok(report) = conn.read_input_report().await {
+            if proxy.send_event(UserEvent::InputReport(report)).is_err() {
+                return;
+            }
+        }
+    })
+}
+
+/// Applies a medium resistance to both triggers for a moment
+async fn test_trigger_feedback(device_manager: &DeviceManager, device_ids: Vec<DeviceId>) {
+    let effect = TriggerEffect::PositionFeedback {
+        start_position: 0,
+        end_position: 255,
+        strength: 128,
+    };
+    let on = DualSenseOutputReportBuilder::new()
+        .left_trigger_effect(effect)
+        .right_trigger_effect(effect)
+        .build();
+    let off = DualSenseOutputReportBuilder::new()
+        .left_trigger_effect(TriggerEffect::Off)
+        .right_trigger_effect(TriggerEffect::Off)
+        .build();
+
+    for device_id in &device_ids {
+        let _ = device_manager
+            .write_output_report(device_id, on.clone())
+            .await;
+    }
+    smol::Timer::after(TRIGGER_TEST_DURATION).await;
+    for device_id in &device_ids {
+        let _ = device_manager
+            .write_output_report(device_id, off.clone())
+            .await;
+    }
+}
+
+/// e.g. `│████████░░│ 80% ⚡`, one block per 10%. Windows tooltips may not render the
+/// block characters, so `[########..] 80% +` is used there.
+fn format_battery_bar(capacity: u8, charging: bool) -> String {
+    let (open, filled, empty, close, charging_symbol) = if cfg!(target_os = "windows") {
+        ('[', '#', '.', ']', '+')
+    } else {
+        ('│', '█', '░', '│', '⚡')
+    };
+    let blocks = (capacity.min(100) / 10) as usize;
+    let mut bar = String::new();
+    bar.push(open);
+    bar.extend(core::iter::repeat_n(filled, blocks));
+    bar.extend(core::iter::repeat_n(empty, 10 - blocks));
+    bar.push(close);
+    let bar = format!("{bar} {capacity}%");
+    if charging {
+        format!("{bar} {charging_symbol}")
+    } else {
+        bar
+    }
+}
+
+/// e.g. "1h 23m", minutes only below an hour
+fn format_uptime(uptime: Duration) -> String {
+    let minutes = uptime.as_secs() / 60;
+    match minutes / 60 {
+        0 => format!("{minutes}m"),
+        hours => format!("{hours}h {}m", minutes % 60),
+    }
+}
+
 fn load_icon(bg: &[u8; 4]) -> tray_icon::Icon {
     const ICON_PNG: &[u8] =
         include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/icon.webp"));
@@ -195,3 +543,19 @@ fn load_icon(bg: &[u8; 4]) -> tray_icon::Icon {
     };
     tray_icon::Icon::from_rgba(rgba, width, height).expect("Failed to open icon")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_bar_blocks() {
+        let bar = format_battery_bar(80, true);
+        if cfg!(target_os = "windows") {
+            assert_eq!(bar, "[########..] 80% +");
+        } else {
+            assert_eq!(bar, "│████████░░│ 80% ⚡");
+        }
+        assert!(!format_battery_bar(0, false).contains(['█', '#']));
+    }
+}
@@ -0,0 +1,117 @@
+use std::{io, net::SocketAddr};
+
+use futures_lite::{Stream, stream};
+use serde::{Deserialize, Serialize};
+use smol::net::UdpSocket;
+use zerocopy::{FromBytes, IntoBytes};
+
+use crate::dualsense::{
+    json::DecodedInputReport,
+    proto::{DS_INPUT_REPORT_SIZE, DualSenseInputReport},
+};
+
+/// Large enough for any packet, datagrams are a few hundred bytes of JSON
+const MAX_DATAGRAM_SIZE: usize = 4096;
+
+/// One datagram per report. `raw` is the report without its id, so receivers can rebuild
+/// the exact `DualSenseInputReport`, `decoded` is there for consumers that only speak JSON.
+#[derive(Debug, Serialize, Deserialize)]
+struct Packet {
+    raw: Vec<u8>,
+    decoded: DecodedInputReport,
+}
+
+/// Sends input reports as JSON datagrams, e.g. to a LAN broadcast address
+pub struct UdpBroadcastServer {
+    socket: UdpSocket,
+    target: SocketAddr,
+}
+
+impl UdpBroadcastServer {
+    pub async fn start(target: SocketAddr) -> io::Result<Self> {
+        let local: SocketAddr = match target {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.set_broadcast(true)?;
+        Ok(Self { socket, target })
+    }
+
+    pub async fn send(&self, report: &DualSenseInputReport) -> io::Result<()> {
+        let packet = Packet {
+            raw: report.as_bytes().to_vec(),
+            decoded: DecodedInputReport::from(report),
+        };
+        let datagram = serde_json::to_vec(&packet).map_err(io::Error::other)?;
+        self.socket.send_to(&datagram, self.target).await?;
+        Ok(())
+    }
+}
+
+/// Receives the reports sent by a `UdpBroadcastServer`
+pub struct UdpBroadcastClient {
+    socket: UdpSocket,
+}
+
+impl UdpBroadcastClient {
+    /// Listens on `addr`, which must match the port the server sends to
+    pub async fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr).await?;
+        Ok(Self { socket })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Datagrams that are not valid packets are skipped, the stream ends on socket errors.
+    pub fn reports(self) -> impl Stream<Item = DualSenseInputReport> {
+        stream::unfold(self.socket, |socket| async move {
+            let mut buf = [0u8; MAX_DATAGRAM_SIZE];
+            loop {
+                let size = socket.recv(&mut buf).await.ok()?;
+                if let Some(report) = parse_packet(&buf[..size]) {
+                    return Some((report, socket));
+                }
+            }
+        })
+    }
+}
+
+fn parse_packet(datagram: &[u8]) -> Option<DualSenseInputReport> {
+    let packet: Packet = serde_json::from_slice(datagram).ok()?;
+    if packet.raw.len() != DS_INPUT_REPORT_SIZE {
+        return None;
+    }
+    DualSenseInputReport::read_from_bytes(&packet.raw).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::StreamExt;
+    use macro_rules_attribute::apply;
+    use smol_macros::test;
+
+    use super::*;
+    use crate::dualsense::proto::DualSenseInputReportBuilder;
+
+    #[apply(test!)]
+    async fn loopback() {
+        let client = UdpBroadcastClient::connect(([127, 0, 0, 1], 0).into())
+            .await
+            .unwrap();
+        let server = UdpBroadcastServer::start(client.local_addr().unwrap())
+            .await
+            .unwrap();
+        let mut reports = Box::pin(client.reports());
+
+        let report = DualSenseInputReportBuilder::new()
+            .left_stick(0x10, 0x20)
+            .right_stick(0x30, 0x40)
+            .build();
+
+        server.send(&report).await.unwrap();
+        assert_eq!(reports.next().await, Some(report));
+    }
+}
@@ -0,0 +1,227 @@
+use std::{num::NonZeroU32, rc::Rc};
+
+use dualsense_tray::dualsense::proto::{
+    Button, DS_TOUCHPAD_HEIGHT, DS_TOUCHPAD_WIDTH, DualSenseInputReport,
+};
+use softbuffer::{Context, SoftBufferError, Surface};
+use tao::{
+    dpi::PhysicalSize,
+    event_loop::EventLoopWindowTarget,
+    window::{Window, WindowBuilder, WindowId},
+};
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 200;
+
+// 0x00RRGGBB, as softbuffer expects
+const BACKGROUND: u32 = 0x202020;
+const OUTLINE: u32 = 0x808080;
+const PRESSED: u32 = 0x3080FF;
+const RELEASED: u32 = 0x404040;
+const TOUCH: u32 = 0xFFFFFF;
+
+const STICK_RADIUS: i32 = 40;
+const LEFT_STICK: (i32, i32) = (70, 140);
+const RIGHT_STICK: (i32, i32) = (250, 140);
+const TRIGGER_BAR: (i32, i32) = (100, 10);
+const LEFT_TRIGGER: (i32, i32) = (20, 10);
+const RIGHT_TRIGGER: (i32, i32) = (200, 10);
+const BUTTON_SIZE: i32 = 14;
+const BUTTONS: (i32, i32) = (20, 32);
+/// Touchpad drawn at 1/16 of its resolution
+const TOUCHPAD: (i32, i32, i32, i32) = (
+    100,
+    56,
+    DS_TOUCHPAD_WIDTH as i32 / 16,
+    DS_TOUCHPAD_HEIGHT as i32 / 16,
+);
+
+/// Small window drawing the live state of a controller, opened from the tray icon
+pub struct Visualizer {
+    window: Rc<Window>,
+    surface: Surface<Rc<Window>, Rc<Window>>,
+    report: Option<DualSenseInputReport>,
+}
+
+impl Visualizer {
+    pub fn open<T>(target: &EventLoopWindowTarget<T>) -> anyhow::Result<Self> {
+        let window = WindowBuilder::new()
+            .with_title("DualSense")
+            .with_inner_size(PhysicalSize::new(WIDTH, HEIGHT))
+            .with_resizable(false)
+            .with_always_on_top(true)
+            .build(target)?;
+        let window = Rc::new(window);
+        let context = Context::new(window.clone()).map_err(softbuffer_error)?;
+        let surface = Surface::new(&context, window.clone()).map_err(softbuffer_error)?;
+        window.set_focus();
+
+        Ok(Self {
+            window,
+            surface,
+            report: None,
+        })
+    }
+
+    pub fn window_id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    pub fn set_report(&mut self, report: DualSenseInputReport) {
+        self.report = Some(report);
+        self.window.request_redraw();
+    }
+
+    pub fn redraw(&mut self) -> anyhow::Result<()> {
+        let size = self.window.inner_size();
+        let (Some(width), Some(height)) =
+            (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+        else {
+            return Ok(());
+        };
+        self.surface
+            .resize(width, height)
+            .map_err(softbuffer_error)?;
+
+        let mut buffer = self.surface.buffer_mut().map_err(softbuffer_error)?;
+        let mut canvas = Canvas {
+            pixels: &mut buffer,
+            width: size.width as i32,
+            height: size.height as i32,
+        };
+        draw(&mut canvas, self.report.as_ref());
+        buffer.present().map_err(softbuffer_error)
+    }
+}
+
+// the platform errors aren't Send, so only the message is kept
+fn softbuffer_error(err: SoftBufferError) -> anyhow::Error {
+    anyhow::anyhow!("{err}")
+}
+
+struct Canvas<'a> {
+    pixels: &'a mut [u32],
+    width: i32,
+    height: i32,
+}
+
+impl Canvas<'_> {
+    /// Pixels outside of the canvas are skipped
+    fn set(&mut self, x: i32, y: i32, color: u32) {
+        if (0..self.width).contains(&x) && (0..self.height).contains(&y) {
+            self.pixels[(y * self.width + x) as usize] = color;
+        }
+    }
+
+    fn fill_rect(&mut self, (x, y): (i32, i32), (width, height): (i32, i32), color: u32) {
+        for py in y..y + height {
+            for px in x..x + width {
+                self.set(px, py, color);
+            }
+        }
+    }
+
+    fn outline_rect(&mut self, (x, y): (i32, i32), (width, height): (i32, i32), color: u32) {
+        for px in x..x + width {
+            self.set(px, y, color);
+            self.set(px, y + height - 1, color);
+        }
+        for py in y..y + height {
+            self.set(x, py, color);
+            self.set(x + width - 1, py, color);
+        }
+    }
+
+    /// Filled when `thickness` is at least `radius`
+    fn circle(&mut self, (cx, cy): (i32, i32), radius: i32, thickness: i32, color: u32) {
+        let inner = (radius - thickness).max(0);
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let distance = dx * dx + dy * dy;
+                if distance <= radius * radius && distance >= inner * inner {
+                    self.set(cx + dx, cy + dy, color);
+                }
+            }
+        }
+    }
+}
+
+/// Sticks as circles with a dot at their position, triggers as bars, one square per button
+/// and the touch points as dots on the touchpad outline
+fn draw(canvas: &mut Canvas, report: Option<&DualSenseInputReport>) {
+    canvas.pixels.fill(BACKGROUND);
+    let Some(report) = report else {
+        return;
+    };
+
+    for (center, (x, y)) in [
+        (LEFT_STICK, report.left_stick()),
+        (RIGHT_STICK, report.right_stick()),
+    ] {
+        canvas.circle(center, STICK_RADIUS, 1, OUTLINE);
+        // positive y points up on the stick, down on screen
+        let dot = (
+            center.0 + (x * STICK_RADIUS as f32) as i32,
+            center.1 - (y * STICK_RADIUS as f32) as i32,
+        );
+        canvas.circle(dot, 4, 4, PRESSED);
+    }
+
+    for (origin, value) in [
+        (LEFT_TRIGGER, report.left_trigger()),
+        (RIGHT_TRIGGER, report.right_trigger()),
+    ] {
+        let filled = (value * TRIGGER_BAR.0 as f32) as i32;
+        canvas.fill_rect(origin, (filled, TRIGGER_BAR.1), PRESSED);
+        canvas.outline_rect(origin, TRIGGER_BAR, OUTLINE);
+    }
+
+    for (i, button) in Button::ALL.into_iter().enumerate() {
+        let color = if report.is_pressed(button) {
+            PRESSED
+        } else {
+            RELEASED
+        };
+        let x = BUTTONS.0 + i as i32 * (BUTTON_SIZE + 4);
+        canvas.fill_rect((x, BUTTONS.1), (BUTTON_SIZE, BUTTON_SIZE), color);
+    }
+
+    let (x, y, width, height) = TOUCHPAD;
+    canvas.outline_rect((x, y), (width, height), OUTLINE);
+    for point in report.sorted_touch_points() {
+        if let Some((px, py)) = point.position_normalized_f64() {
+            let marker = (
+                x + (px * width as f64) as i32,
+                y + (py * height as f64) as i32,
+            );
+            canvas.circle(marker, 3, 3, TOUCH);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dualsense_tray::dualsense::proto::DualSenseInputReportBuilder;
+
+    use super::*;
+
+    #[test]
+    fn stick_dot_follows_stick() {
+        let mut pixels = vec![0; (WIDTH * HEIGHT) as usize];
+        let mut canvas = Canvas {
+            pixels: &mut pixels,
+            width: WIDTH as i32,
+            height: HEIGHT as i32,
+        };
+        // left stick fully right
+        let report = DualSenseInputReportBuilder::new()
+            .left_stick(0xFF, 0x80)
+            .build();
+        draw(&mut canvas, Some(&report));
+
+        let pixel = |(x, y): (i32, i32)| pixels[(y * WIDTH as i32 + x) as usize];
+        assert_eq!(pixel((LEFT_STICK.0 + STICK_RADIUS, LEFT_STICK.1)), PRESSED);
+        assert_eq!(pixel(LEFT_STICK), BACKGROUND);
+        assert_eq!(pixel(RIGHT_STICK), PRESSED);
+    }
+}
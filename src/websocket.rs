@@ -0,0 +1,302 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_hid::DeviceId;
+use async_io::Timer;
+use async_tungstenite::{
+    WebSocketStream,
+    tungstenite::{Message, handshake::derive_accept_key, protocol::Role},
+};
+use futures_lite::{AsyncReadExt, AsyncWriteExt, FutureExt, StreamExt};
+use futures_util::SinkExt;
+use serde::Serialize;
+use smol::{
+    Task,
+    channel::{self, Sender},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    device_manager::DeviceManager,
+    dualsense::{async_hid::DualSense, json::DecodedInputReport, proto::DualSenseInputReport},
+    net::{TcpServer, accept_with_backoff},
+};
+
+const VISUALIZER_HTML: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/visualizer.html"
+));
+
+/// How often the list of opened devices is checked for new controllers
+const DEVICE_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// Reports queued per client, reports to a client that falls behind are dropped
+const CLIENT_QUEUE: usize = 16;
+const MAX_REQUEST_SIZE: usize = 8192;
+
+#[derive(Serialize)]
+struct ReportMessage<'a> {
+    device: &'a str,
+    report: DecodedInputReport,
+}
+
+type Clients = Arc<Mutex<Vec<Sender<Arc<str>>>>>;
+
+/// Pushes the reports of every opened device as JSON to WebSocket clients. Plain HTTP
+/// requests on the same port get a page that visualizes them.
+pub struct WebSocketServer {
+    local_addr: SocketAddr,
+    clients: Clients,
+    _accept_task: Task<()>,
+    _read_task: Task<()>,
+}
+
+impl WebSocketServer {
+    /// Listens on `addr`. There is no authentication, so anything but a loopback address
+    /// such as `127.0.0.1` streams the controller input to the whole network.
+    pub async fn start(addr: SocketAddr, manager: Arc<DeviceManager>) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let clients: Clients = Arc::new(Mutex::new(Vec::new()));
+
+        Ok(Self {
+            local_addr,
+            clients: clients.clone(),
+            _accept_task: smol::spawn(accept(listener, clients.clone())),
+            _read_task: smol::spawn(read_devices(manager, clients)),
+        })
+    }
+}
+
+impl TcpServer for WebSocketServer {
+    fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+async fn accept(listener: TcpListener, clients: Clients) {
+    loop {
+        let stream = accept_with_backoff(|| listener.accept(), "WebSocket client").await;
+        let clients = clients.clone();
+        smol::spawn(async move {
+            if let Err(err) = serve_client(stream, clients).await {
+                tracing::debug!("WebSocket client disconnected: {err}");
+            }
+        })
+        .detach();
+    }
+}
+
+async fn serve_client(mut stream: TcpStream, clients: Clients) -> io::Result<()> {
+    let request = read_request(&mut stream).await?;
+    let Some(key) = websocket_key(&request) else {
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            VISUALIZER_HTML.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(VISUALIZER_HTML.as_bytes()).await?;
+        return stream.flush().await;
+    };
+
+    let head = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    );
+    stream.write_all(head.as_bytes()).await?;
+    let mut ws = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+
+    let (sender, receiver) = channel::bounded(CLIENT_QUEUE);
+    clients.lock().unwrap().push(sender);
+    // the sender is dropped from `clients` once the receiver is gone
+    loop {
+        let event = async { ClientEvent::Frame(ws.next().await) }
+            .or(async { ClientEvent::Report(receiver.recv().await.ok()) })
+            .await;
+        match event {
+            // tungstenite queues the pong and close replies itself and sends them on the
+            // next read, so frames only have to keep being read
+            ClientEvent::Frame(Some(Ok(_))) => {}
+            ClientEvent::Frame(Some(Err(err))) => return Err(io::Error::other(err)),
+            // the close handshake completed
+            ClientEvent::Frame(None) => return Ok(()),
+            ClientEvent::Report(Some(message)) => {
+                ws.send(Message::text(message.as_ref()))
+                    .await
+                    .map_err(io::Error::other)?;
+            }
+            ClientEvent::Report(None) => return Ok(()),
+        }
+    }
+}
+
+enum ClientEvent {
+    Frame(Option<Result<Message, async_tungstenite::tungstenite::Error>>),
+    Report(Option<Arc<str>>),
+}
+
+/// Reads the request head, clients don't send anything else before the handshake completes
+async fn read_request(stream: &mut TcpStream) -> io::Result<String> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.ends_with(b"\r\n\r\n") {
+        let size = stream.read(&mut buf).await?;
+        if size == 0 || request.len() + size > MAX_REQUEST_SIZE {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        request.extend_from_slice(&buf[..size]);
+    }
+    String::from_utf8(request).map_err(|_| io::ErrorKind::InvalidData.into())
+}
+
+fn websocket_key(request: &str) -> Option<&str> {
+    request.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("sec-websocket-key")
+            .then(|| value.trim())
+    })
+}
+
+/// Keeps a reader running for every opened device while clients are connected
+async fn read_devices(manager: Arc<DeviceManager>, clients: Clients) {
+    let mut readers: HashMap<DeviceId, Task<()>> = HashMap::new();
+    loop {
+        if !has_clients(&clients) {
+            // dropping the tasks closes the connections to the devices
+            readers.clear();
+            Timer::after(DEVICE_REFRESH_INTERVAL).await;
+            continue;
+        }
+        let devices = manager.opened_devices().await;
+        readers.retain(|device_id, task| {
+            !task.is_finished() && devices.iter().any(|(id, _)| id == device_id)
+        });
+        for (device_id, device) in devices {
+            readers
+                .entry(device_id)
+                .or_insert_with(|| smol::spawn(read_device(device, clients.clone())));
+        }
+        Timer::after(DEVICE_REFRESH_INTERVAL).await;
+    }
+}
+
+async fn read_device(device: Arc<DualSense>, clients: Clients) {
+    let Ok(mut ds_conn) = device.connect().await else {
+        return;
+    };
+    let name = format!("{:?}", device.device_id());
+    while let Ok(report) = ds_conn.read_input_report().await {
+        // `read_devices` starts reading again once a client connects
+        if !broadcast(&clients, &name, &report) {
+            return;
+        }
+    }
+}
+
+fn has_clients(clients: &Clients) -> bool {
+    let mut clients = clients.lock().unwrap();
+    clients.retain(|client| !client.is_closed());
+    !clients.is_empty()
+}
+
+/// Queues `report` for every client, false once none are connected
+fn broadcast(clients: &Clients, device: &str, report: &DualSenseInputReport) -> bool {
+    let mut clients = clients.lock().unwrap();
+    clients.retain(|client| !client.is_closed());
+    if clients.is_empty() {
+        return false;
+    }
+    let message = ReportMessage {
+        device,
+        report: DecodedInputReport::from(report),
+    };
+    if let Ok(message) = serde_json::to_string(&message) {
+        let message: Arc<str> = message.into();
+        for client in clients.iter() {
+            let _ = client.try_send(message.clone());
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use async_tungstenite::client_async;
+    use macro_rules_attribute::apply;
+    use smol_macros::test;
+
+    use super::*;
+    use crate::dualsense::proto::{Button, DualSenseInputReportBuilder};
+
+    async fn connect(server: &WebSocketServer) -> WebSocketStream<TcpStream> {
+        let addr = server.local_addr();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (ws, _) = client_async(format!("ws://{addr}/"), stream).await.unwrap();
+        // the server registers the client right after answering the handshake
+        while !has_clients(&server.clients) {
+            Timer::after(Duration::from_millis(1)).await;
+        }
+        ws
+    }
+
+    #[apply(test!)]
+    async fn handshake_and_broadcast() {
+        let manager = Arc::new(DeviceManager::new());
+        let server = WebSocketServer::start(([127, 0, 0, 1], 0).into(), manager)
+            .await
+            .unwrap();
+        assert!(!broadcast(
+            &server.clients,
+            "none",
+            &DualSenseInputReportBuilder::new().build()
+        ));
+
+        let mut ws = connect(&server).await;
+        let report = DualSenseInputReportBuilder::new()
+            .button(Button::Cross, true)
+            .build();
+        assert!(broadcast(&server.clients, "test", &report));
+
+        let message = ws.next().await.unwrap().unwrap();
+        let json: serde_json::Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        assert_eq!(json["device"], "test");
+        assert_eq!(
+            json["report"],
+            serde_json::to_value(DecodedInputReport::from(&report)).unwrap()
+        );
+    }
+
+    #[apply(test!)]
+    async fn answers_ping_and_close() {
+        let manager = Arc::new(DeviceManager::new());
+        let server = WebSocketServer::start(([127, 0, 0, 1], 0).into(), manager)
+            .await
+            .unwrap();
+        let mut ws = connect(&server).await;
+
+        ws.send(Message::Ping(vec![1, 2, 3].into())).await.unwrap();
+        match ws.next().await.unwrap().unwrap() {
+            Message::Pong(payload) => assert_eq!(&payload[..], [1, 2, 3]),
+            message => panic!("expected a pong, got {message:?}"),
+        }
+
+        ws.close(None).await.unwrap();
+        while let Some(Ok(_)) = ws.next().await {}
+        // the server side receiver is dropped once its client is gone
+        while has_clients(&server.clients) {
+            Timer::after(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[test]
+    fn finds_websocket_key() {
+        let request = "GET / HTTP/1.1\r\nHost: localhost\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n";
+        assert_eq!(websocket_key(request), Some("dGhlIHNhbXBsZSBub25jZQ=="));
+        assert_eq!(websocket_key("GET / HTTP/1.1\r\n\r\n"), None);
+    }
+}